@@ -0,0 +1,93 @@
+//! Compares lookups on items whose key must be extracted at every
+//! comparison with lookups on items storing their key inline.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use raw_btree::RawBTree;
+use std::cmp::Ordering;
+
+const LEN: u64 = 10_000;
+
+/// Item whose key is parsed from its payload at every comparison.
+struct Encoded {
+	payload: Vec<u8>,
+}
+
+impl Encoded {
+	fn key(&self) -> u64 {
+		std::str::from_utf8(&self.payload)
+			.unwrap()
+			.trim_start_matches('0')
+			.parse()
+			.unwrap_or(0)
+	}
+
+	fn cmp(a: &Self, b: &Self) -> Ordering {
+		a.key().cmp(&b.key())
+	}
+
+	fn key_cmp(item: &Self, key: &u64) -> Ordering {
+		item.key().cmp(key)
+	}
+}
+
+/// Item storing its key inline, next to the same payload.
+struct Inline {
+	key: u64,
+	#[allow(dead_code)]
+	payload: Vec<u8>,
+}
+
+impl Inline {
+	fn cmp(a: &Self, b: &Self) -> Ordering {
+		a.key.cmp(&b.key)
+	}
+
+	fn key_cmp(item: &Self, key: &u64) -> Ordering {
+		item.key.cmp(key)
+	}
+}
+
+fn payload(key: u64) -> Vec<u8> {
+	format!("{key:032}").into_bytes()
+}
+
+fn lookup(c: &mut Criterion) {
+	let mut encoded: RawBTree<Encoded> = RawBTree::new();
+	let mut inline: RawBTree<Inline> = RawBTree::new();
+	for key in 0..LEN {
+		encoded.insert(
+			Encoded::cmp,
+			Encoded {
+				payload: payload(key),
+			},
+		);
+		inline.insert(
+			Inline::cmp,
+			Inline {
+				key,
+				payload: payload(key),
+			},
+		);
+	}
+
+	let keys: Vec<u64> = (0..LEN).step_by(7).collect();
+
+	let mut group = c.benchmark_group("get");
+	group.bench_function("extracted key", |b| {
+		b.iter(|| {
+			for key in &keys {
+				black_box(encoded.get(Encoded::key_cmp, black_box(key)));
+			}
+		})
+	});
+	group.bench_function("inline key", |b| {
+		b.iter(|| {
+			for key in &keys {
+				black_box(inline.get(Inline::key_cmp, black_box(key)));
+			}
+		})
+	});
+	group.finish()
+}
+
+criterion_group!(benches, lookup);
+criterion_main!(benches);
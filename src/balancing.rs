@@ -1,20 +1,57 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, marker::PhantomData};
 
 use crate::{
-	node::{Address, Balance, WouldUnderflow},
-	Node, Storage,
+	node::{Address, Balance, SplitBias, WouldUnderflow},
+	storage::RemovedItem,
+	Node, Storage, M,
 };
 
+/// Structural operation performed while rebalancing a tree.
+///
+/// Reported to the observer registered with
+/// `RawBTree::with_rebalance_observer`, available with the
+/// `rebalance-observer` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RebalanceEvent<N> {
+	/// The overflowing `node` has been split, its right half being moved to
+	/// `new_node`.
+	Split { node: N, new_node: N },
+
+	/// An item of the right sibling of the `deficient` child of `parent` has
+	/// been rotated into it.
+	RotateLeft { parent: N, deficient: N },
+
+	/// An item of the left sibling of the `deficient` child of `parent` has
+	/// been rotated into it.
+	RotateRight { parent: N, deficient: N },
+
+	/// The `right` child of `parent` has been merged into the `left` one, and
+	/// released.
+	Merge { parent: N, left: N, right: N },
+}
+
+/// Optional rebalance observer.
+pub(crate) type Observer<'a, N> = Option<&'a mut dyn FnMut(RebalanceEvent<N>)>;
+
+/// Rebalance the tree, reporting every structural operation to the given
+/// observer.
+///
 /// # Safety
 ///
 /// Input nodes must not have been deallocated.
-#[inline]
-pub unsafe fn rebalance<T, S: Storage<T>>(
+pub(crate) unsafe fn rebalance_observed<T, S: Storage<T>>(
 	tree: &mut S,
 	mut root: Option<S::Node>,
 	mut id: S::Node,
 	mut addr: Address<S::Node>,
+	mut observer: Observer<S::Node>,
 ) -> (Option<S::Node>, Option<Address<S::Node>>) {
+	let mut emit = |event| {
+		if let Some(observer) = &mut observer {
+			observer(event)
+		}
+	};
+
 	let mut balance = tree.get(id).balance();
 
 	let addr = loop {
@@ -22,8 +59,21 @@ pub unsafe fn rebalance<T, S: Storage<T>>(
 			Balance::Balanced => break Some(addr),
 			Balance::Overflow => {
 				assert!(!tree.get_mut(id).is_underflowing());
-				let (median_offset, median, right_node) = tree.get_mut(id).split();
+
+				// If the addressed item is the last of the node, items are
+				// likely inserted sequentially: keep the left node full.
+				let bias = if addr.node == id && addr.offset == tree.get(id).item_count() - 1 {
+					SplitBias::Right
+				} else {
+					SplitBias::Middle
+				};
+
+				let (median_offset, median, right_node) = tree.get_mut(id).split_biased(bias);
 				let right_id = tree.insert_node(right_node);
+				emit(RebalanceEvent::Split {
+					node: id,
+					new_node: right_id,
+				});
 
 				match tree.get(id).parent() {
 					Some(parent_id) => {
@@ -93,13 +143,22 @@ pub unsafe fn rebalance<T, S: Storage<T>>(
 						let index = tree.get(parent_id).child_index(id).unwrap();
 						// An underflow append in the child node.
 						// First we try to rebalance the tree by rotation.
-						if try_rotate_left(tree, parent_id, index, &mut addr)
-							|| try_rotate_right(tree, parent_id, index, &mut addr)
-						{
+						if try_rotate_left(tree, parent_id, index, &mut addr) {
+							emit(RebalanceEvent::RotateLeft {
+								parent: parent_id,
+								deficient: id,
+							});
+							break Some(addr);
+						} else if try_rotate_right(tree, parent_id, index, &mut addr) {
+							emit(RebalanceEvent::RotateRight {
+								parent: parent_id,
+								deficient: id,
+							});
 							break Some(addr);
 						} else {
 							// Rotation didn't work.
 							// This means that all existing child sibling have enough few elements to be merged with this child.
+							emit(merge_event(tree, parent_id, index));
 							let (new_balance, new_addr) = merge(tree, parent_id, index, addr);
 							balance = new_balance;
 							addr = new_addr;
@@ -149,7 +208,7 @@ pub unsafe fn rebalance<T, S: Storage<T>>(
 /// Returns true if the rotation succeeded, of false if the target child has no right sibling,
 /// or if this sibling would underflow.
 #[inline]
-unsafe fn try_rotate_left<T, S: Storage<T>>(
+pub(crate) unsafe fn try_rotate_left<T, S: Storage<T>>(
 	tree: &mut S,
 	id: S::Node,
 	deficient_child_index: usize,
@@ -269,9 +328,29 @@ unsafe fn try_rotate_right<T, S: Storage<T>>(
 	}
 }
 
+/// Returns the event describing the merge performed by [`merge`] with the
+/// same arguments.
+///
+/// # Safety
+///
+/// Input nodes must not have been deallocated.
+pub(crate) unsafe fn merge_event<T, S: Storage<T>>(
+	tree: &S,
+	id: S::Node,
+	deficient_child_index: usize,
+) -> RebalanceEvent<S::Node> {
+	let left_index = deficient_child_index.saturating_sub(1);
+	let node = tree.get(id);
+	RebalanceEvent::Merge {
+		parent: id,
+		left: node.child_id(left_index),
+		right: node.child_id(left_index + 1),
+	}
+}
+
 /// Merge the child `deficient_child_index` in node `id` with one of its direct sibling.
 #[inline]
-unsafe fn merge<T, S: Storage<T>>(
+pub(crate) unsafe fn merge<T, S: Storage<T>>(
 	tree: &mut S,
 	id: S::Node,
 	deficient_child_index: usize,
@@ -311,3 +390,244 @@ unsafe fn merge<T, S: Storage<T>>(
 
 	(balance, addr)
 }
+
+/// Border of a tree.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Border {
+	/// Path from the root to the first leaf.
+	Left,
+
+	/// Path from the root to the last leaf.
+	Right,
+}
+
+/// Removes the root while it has no item, and returns the new root.
+///
+/// # Safety
+///
+/// Input nodes must not have been deallocated.
+pub(crate) unsafe fn fix_top<T, S: Storage<T>>(
+	tree: &mut S,
+	mut root: Option<S::Node>,
+) -> Option<S::Node> {
+	while let Some(id) = root {
+		let node = tree.get(id);
+		if node.item_count() > 0 {
+			break;
+		}
+
+		root = node.child_id_opt(0);
+		if let Some(child_id) = root {
+			tree.get_mut(child_id).set_parent(None)
+		}
+
+		tree.release_node(id);
+	}
+
+	root
+}
+
+/// Fixes the nodes of the given border of a tree that has just been split,
+/// and returns the new root.
+///
+/// Nodes out of the border must not underflow, and nodes of the border may
+/// hold any number of items (even none) without overflowing. Going down
+/// from the root, each border child is merged with its sibling if possible.
+/// Otherwise, items of the sibling are rotated into it until it holds enough
+/// items to afford a merge of its own border child.
+///
+/// # Safety
+///
+/// Input nodes must not have been deallocated.
+pub(crate) unsafe fn fix_border<T, S: Storage<T>>(
+	tree: &mut S,
+	root: Option<S::Node>,
+	border: Border,
+	mut observer: Observer<S::Node>,
+) -> Option<S::Node> {
+	let mut emit = |event| {
+		if let Some(observer) = &mut observer {
+			observer(event)
+		}
+	};
+
+	let root = fix_top(tree, root);
+	let mut id = root?;
+
+	while !tree.get(id).is_leaf() {
+		let node = tree.get(id);
+		let (index, sibling_index) = match border {
+			Border::Left => (0, 1),
+			Border::Right => (node.child_count() - 1, node.child_count() - 2),
+		};
+
+		let child_id = node.child_id(index);
+		let sibling_id = node.child_id(sibling_index);
+		let sibling = tree.get(sibling_id);
+		let count = sibling.item_count() + 1 + tree.get(child_id).item_count();
+		let can_merge = if sibling.is_leaf() {
+			count <= M
+		} else {
+			count < M
+		};
+
+		let mut addr = Address::new(child_id, 0.into());
+		if can_merge {
+			emit(merge_event(tree, id, index));
+			merge(tree, id, index, addr);
+			id = match border {
+				Border::Left => child_id,
+				Border::Right => sibling_id,
+			}
+		} else {
+			// Leave enough items for the border child to be merged without
+			// underflowing, which the sibling can afford since it can't be
+			// merged.
+			while tree.get(child_id).item_count() < M / 2 {
+				match border {
+					Border::Left => {
+						assert!(try_rotate_left(tree, id, index, &mut addr));
+						emit(RebalanceEvent::RotateLeft {
+							parent: id,
+							deficient: child_id,
+						})
+					}
+					Border::Right => {
+						assert!(try_rotate_right(tree, id, index, &mut addr));
+						emit(RebalanceEvent::RotateRight {
+							parent: id,
+							deficient: child_id,
+						})
+					}
+				}
+			}
+
+			id = child_id
+		}
+	}
+
+	// Merging the root children may have emptied the root.
+	fix_top(tree, root)
+}
+
+/// Inserts the given item exactly at the provided **leaf** address,
+/// reporting the rebalancing operations to the given observer.
+///
+/// # Safety
+///
+/// Input nodes must not have been deallocated.
+pub(crate) unsafe fn insert_exactly_at_observed<T, S: Storage<T>>(
+	tree: &mut S,
+	root: Option<S::Node>,
+	addr: Option<Address<S::Node>>,
+	item: T,
+	opt_right_id: Option<S::Node>,
+	observer: Observer<S::Node>,
+) -> (Option<S::Node>, Option<Address<S::Node>>) {
+	match addr {
+		Some(addr) => {
+			tree.get_mut(addr.node)
+				.insert(addr.offset, item, opt_right_id);
+			rebalance_observed(tree, root, addr.node, addr, observer)
+		}
+		None => {
+			let new_root = Node::leaf(None, item);
+			let id = tree.insert_node(new_root);
+			let addr = Address {
+				node: id,
+				offset: 0.into(),
+			};
+			(Some(id), Some(addr))
+		}
+	}
+}
+
+/// Removes the item at the given address, reporting the rebalancing
+/// operations to the given observer.
+///
+/// # Safety
+///
+/// Input nodes must not have been deallocated.
+pub(crate) unsafe fn remove_at_observed<T, S: Storage<T>>(
+	tree: &mut S,
+	root: Option<S::Node>,
+	addr: Address<S::Node>,
+	observer: Observer<S::Node>,
+) -> Option<RemovedItem<T, S>> {
+	match tree.get_mut(addr.node).leaf_remove(addr.offset) {
+		Some(Ok(item)) => {
+			// removed from a leaf.
+			let (new_root, new_addr) = rebalance_observed(tree, root, addr.node, addr, observer);
+			Some(RemovedItem {
+				new_root,
+				item,
+				new_addr,
+			})
+		}
+		Some(Err(left_child_id)) => {
+			// removed from an internal node.
+			let new_addr = tree.next_item_or_back_address(addr).unwrap();
+			let (separator, leaf_id) = tree.remove_rightmost_leaf_of(left_child_id);
+			let item = tree.get_mut(addr.node).replace(addr.offset, separator);
+			let (new_root, new_addr) = rebalance_observed(tree, root, leaf_id, new_addr, observer);
+			Some(RemovedItem {
+				new_root,
+				item,
+				new_addr,
+			})
+		}
+		None => None,
+	}
+}
+
+/// Rebalance observer owned by a tree.
+#[cfg(feature = "rebalance-observer")]
+struct BoxedObserver<N>(Box<dyn FnMut(RebalanceEvent<N>) + Send>);
+
+// The observer is only ever accessed through a mutable reference.
+#[cfg(feature = "rebalance-observer")]
+unsafe impl<N> Sync for BoxedObserver<N> {}
+
+/// Optional rebalance observer owned by a tree.
+///
+/// Without the `rebalance-observer` feature, no observer can be set and this
+/// type is zero-sized.
+pub(crate) struct ObserverSlot<N> {
+	#[cfg(feature = "rebalance-observer")]
+	observer: Option<BoxedObserver<N>>,
+
+	node: PhantomData<N>,
+}
+
+impl<N> ObserverSlot<N> {
+	/// Creates a slot without observer.
+	pub fn new() -> Self {
+		Self {
+			#[cfg(feature = "rebalance-observer")]
+			observer: None,
+			node: PhantomData,
+		}
+	}
+
+	/// Sets the observer, replacing any previous one.
+	#[cfg(feature = "rebalance-observer")]
+	pub fn set(&mut self, f: impl FnMut(RebalanceEvent<N>) + Send + 'static) {
+		self.observer = Some(BoxedObserver(Box::new(f)))
+	}
+
+	/// Removes the observer, if any.
+	#[cfg(feature = "rebalance-observer")]
+	pub fn clear(&mut self) {
+		self.observer = None
+	}
+
+	/// Borrows the observer, if any.
+	pub fn get(&mut self) -> Observer<'_, N> {
+		#[cfg(feature = "rebalance-observer")]
+		if let Some(observer) = &mut self.observer {
+			return Some(&mut *observer.0);
+		}
+
+		None
+	}
+}
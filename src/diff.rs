@@ -0,0 +1,12 @@
+/// Difference between two trees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Diff<T> {
+	/// Item only found in the left tree.
+	Left(T),
+
+	/// Item only found in the right tree.
+	Right(T),
+
+	/// Items comparing equal in both trees, but with different content.
+	Changed(T, T),
+}
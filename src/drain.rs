@@ -0,0 +1,89 @@
+use std::{cmp::Ordering, iter::FusedIterator, marker::PhantomData, ops::RangeBounds};
+
+use crate::{storage::BoxStorage, utils::is_below_upper_bound, Address, RawBTree, Storage};
+
+/// Iterator removing the items of a tree in a given range.
+///
+/// Created by [`RawBTree::drain_range`]. If the iterator is dropped before
+/// being fully consumed, the remaining items in the range are removed and
+/// dropped.
+pub struct DrainRange<'a, T, C, R, Q: ?Sized, S: Storage<T> = BoxStorage>
+where
+	C: Fn(&T, &Q) -> Ordering,
+	R: RangeBounds<Q>,
+{
+	/// The tree reference.
+	btree: &'a mut RawBTree<T, S>,
+
+	/// Comparison function.
+	cmp: C,
+
+	/// Drained range.
+	range: R,
+
+	/// Address of the next item.
+	addr: Option<Address<S::Node>>,
+
+	key: PhantomData<fn(&Q)>,
+}
+
+impl<'a, T, C, R, Q: ?Sized, S: Storage<T>> DrainRange<'a, T, C, R, Q, S>
+where
+	C: Fn(&T, &Q) -> Ordering,
+	R: RangeBounds<Q>,
+{
+	#[inline]
+	pub(crate) fn new(btree: &'a mut RawBTree<T, S>, cmp: C, range: R) -> Self {
+		let addr = btree.lower_bound_address(&cmp, range.start_bound());
+		Self {
+			btree,
+			cmp,
+			range,
+			addr,
+			key: PhantomData,
+		}
+	}
+}
+
+impl<'a, T, C, R, Q: ?Sized, S: Storage<T>> Iterator for DrainRange<'a, T, C, R, Q, S>
+where
+	C: Fn(&T, &Q) -> Ordering,
+	R: RangeBounds<Q>,
+{
+	type Item = T;
+
+	#[inline]
+	fn next(&mut self) -> Option<T> {
+		let addr = self.addr?;
+		let item = unsafe { self.btree.get_at(addr).unwrap() };
+		if !is_below_upper_bound(&self.cmp, item, self.range.end_bound()) {
+			self.addr = None;
+			return None;
+		}
+
+		let r = unsafe { self.btree.remove_at(addr).unwrap() };
+		self.btree.root = r.new_root;
+		self.btree.len -= 1;
+		self.addr = r
+			.new_addr
+			.and_then(|addr| unsafe { self.btree.nodes.normalize(addr) });
+		Some(r.item)
+	}
+}
+
+impl<'a, T, C, R, Q: ?Sized, S: Storage<T>> FusedIterator for DrainRange<'a, T, C, R, Q, S>
+where
+	C: Fn(&T, &Q) -> Ordering,
+	R: RangeBounds<Q>,
+{
+}
+
+impl<'a, T, C, R, Q: ?Sized, S: Storage<T>> Drop for DrainRange<'a, T, C, R, Q, S>
+where
+	C: Fn(&T, &Q) -> Ordering,
+	R: RangeBounds<Q>,
+{
+	fn drop(&mut self) {
+		self.for_each(std::mem::drop)
+	}
+}
@@ -0,0 +1,115 @@
+use crate::{storage::BoxStorage, Address, RawBTree, Storage};
+
+/// Occupied entry of a B-Tree.
+///
+/// Gives mutable access to an item of the tree with the option to remove it.
+pub struct OccupiedEntry<'a, T, S: Storage<T> = BoxStorage> {
+	/// The tree reference.
+	btree: &'a mut RawBTree<T, S>,
+
+	/// Address of the item.
+	addr: Address<S::Node>,
+}
+
+impl<'a, T, S: Storage<T>> OccupiedEntry<'a, T, S> {
+	/// Creates a new entry.
+	///
+	/// # Safety
+	///
+	/// The address must be an occupied item address in the given tree.
+	#[inline]
+	pub(crate) unsafe fn new(btree: &'a mut RawBTree<T, S>, addr: Address<S::Node>) -> Self {
+		Self { btree, addr }
+	}
+
+	/// Returns the address of the entry's item.
+	#[inline]
+	pub fn address(&self) -> Address<S::Node> {
+		self.addr
+	}
+
+	/// Returns a reference to the entry's item.
+	#[inline]
+	pub fn get(&self) -> &T {
+		unsafe { self.btree.get_at(self.addr).unwrap() }
+	}
+
+	/// Returns a mutable reference to the entry's item.
+	#[inline]
+	pub fn get_mut(&mut self) -> &mut T {
+		unsafe { self.btree.get_mut_at(self.addr).unwrap() }
+	}
+
+	/// Converts the entry into a mutable reference to its item.
+	#[inline]
+	pub fn into_mut(self) -> &'a mut T {
+		unsafe { self.btree.get_mut_at(self.addr).unwrap() }
+	}
+
+	/// Replaces the entry's item, returning the old one.
+	///
+	/// The new item must compare equal to the old one.
+	#[inline]
+	pub fn insert(&mut self, item: T) -> T {
+		unsafe { self.btree.nodes.replace_at(self.addr, item) }
+	}
+
+	/// Removes the entry's item from the tree and returns it.
+	#[inline]
+	pub fn remove(self) -> T {
+		unsafe { self.btree.take_at(self.addr) }
+	}
+}
+
+/// Reserved insertion point in a B-Tree.
+///
+/// Returned by [`RawBTree::reserve_insertion`]. The tree is exclusively
+/// borrowed until the slot is committed or aborted, so the reserved address
+/// stays valid.
+pub struct ReservedSlot<'a, T, S: Storage<T> = BoxStorage> {
+	/// The tree reference.
+	btree: &'a mut RawBTree<T, S>,
+
+	/// Insertion address, `None` if the tree is empty.
+	addr: Option<Address<S::Node>>,
+}
+
+impl<'a, T, S: Storage<T>> ReservedSlot<'a, T, S> {
+	/// Creates a new reserved slot.
+	///
+	/// # Safety
+	///
+	/// The address must be a leaf insertion address in the given tree, or
+	/// `None` if the tree is empty.
+	#[inline]
+	pub(crate) unsafe fn new(
+		btree: &'a mut RawBTree<T, S>,
+		addr: Option<Address<S::Node>>,
+	) -> Self {
+		Self { btree, addr }
+	}
+
+	/// Returns the reserved insertion address.
+	#[inline]
+	pub fn address(&self) -> Option<Address<S::Node>> {
+		self.addr
+	}
+
+	/// Inserts the given item at the reserved address, and returns a mutable
+	/// reference to it.
+	///
+	/// # Safety
+	///
+	/// The item must compare equal to the key used to reserve the slot,
+	/// otherwise it is inserted out of order and the tree is left invalid.
+	pub unsafe fn commit(self, item: T) -> &'a mut T {
+		let (root, addr) = self.btree.insert_exactly_at(self.addr, item);
+		self.btree.root = root;
+		self.btree.len += 1;
+		self.btree.get_mut_at(addr.unwrap()).unwrap()
+	}
+
+	/// Releases the slot without inserting anything.
+	#[inline]
+	pub fn abort(self) {}
+}
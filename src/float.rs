@@ -0,0 +1,19 @@
+use std::cmp::Ordering;
+
+/// Compares two `f64` according to the IEEE 754 total ordering.
+///
+/// Unlike `f64::partial_cmp`, this is a total order (`NaN` values included)
+/// that can be used as a [`RawBTree`](crate::RawBTree) comparison function.
+#[inline]
+pub fn total_f64_cmp(a: &f64, b: &f64) -> Ordering {
+	a.total_cmp(b)
+}
+
+/// Compares two `f32` according to the IEEE 754 total ordering.
+///
+/// Unlike `f32::partial_cmp`, this is a total order (`NaN` values included)
+/// that can be used as a [`RawBTree`](crate::RawBTree) comparison function.
+#[inline]
+pub fn total_f32_cmp(a: &f32, b: &f32) -> Ordering {
+	a.total_cmp(b)
+}
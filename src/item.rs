@@ -1,5 +1,7 @@
 use std::{borrow::Borrow, cmp::Ordering};
 
+use crate::{RawBTree, Storage};
+
 #[derive(Debug, Clone)]
 pub struct Item<K, V> {
 	pub key: K,
@@ -45,3 +47,28 @@ impl<K: std::fmt::Display, V: std::fmt::Display> std::fmt::Display for Item<K, V
 		write!(f, "({}, {})", self.key, self.value)
 	}
 }
+
+impl<K: Ord, V, S: Storage<Item<K, V>> + Default> RawBTree<Item<K, V>, S> {
+	/// Creates a tree from the given key-value pairs.
+	///
+	/// If a key appears more than once, the last value is kept. Pairs sorted
+	/// by key are appended without searching the tree.
+	pub fn from_kv_iter(iter: impl IntoIterator<Item = (K, V)>) -> Self {
+		let mut result = Self::new();
+		for (key, value) in iter {
+			result.push_back(Item::cmp, Item::new(key, value));
+		}
+
+		result
+	}
+}
+
+impl<K, V, S: Storage<Item<K, V>>> RawBTree<Item<K, V>, S> {
+	/// Returns the keys of the first and last items of the tree.
+	///
+	/// See [`Self::key_span`].
+	#[inline]
+	pub fn item_key_span(&self) -> Option<(&K, &K)> {
+		self.key_span(|item| &item.key)
+	}
+}
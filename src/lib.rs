@@ -6,15 +6,51 @@ pub(crate) mod utils;
 
 pub mod node;
 pub use node::{Address, Node};
-use std::{cmp::Ordering, iter::FusedIterator, marker::PhantomData};
+use std::{
+	borrow::{Borrow, Cow},
+	cmp::Ordering,
+	hash::{Hash, Hasher},
+	iter::FusedIterator,
+	marker::PhantomData,
+	ops::{Bound, ControlFlow, RangeBounds},
+};
+
+#[cfg(feature = "rebalance-observer")]
+use std::sync::{Arc, Mutex};
 
 mod balancing;
+mod diff;
+mod drain;
+mod entry;
+mod float;
 mod item;
+mod log;
+mod order;
+mod ordered;
+mod prefix;
+mod reverse;
+mod slice_index;
+mod stats;
 pub mod storage;
-
+mod subtree;
+
+pub use balancing::RebalanceEvent;
+use balancing::{Border, ObserverSlot};
+pub use diff::Diff;
+pub use drain::DrainRange;
+pub use entry::{OccupiedEntry, ReservedSlot};
+pub use float::{total_f32_cmp, total_f64_cmp};
 pub use item::Item;
-use storage::BoxStorage;
+pub use log::LogOp;
+pub use order::{check_total_order, OrderViolation};
+pub use ordered::OrderedTree;
+pub use prefix::PrefixKey;
+pub use reverse::{reverse_cmp, Reverse};
+pub use slice_index::SliceIndex;
+pub use stats::{LocalityStats, RebalanceCounters, TreeStats};
 pub use storage::Storage;
+use storage::{BoxStorage, CowStorage};
+pub use subtree::SubtreeView;
 
 use crate::utils::Array;
 
@@ -23,6 +59,36 @@ use crate::utils::Array;
 /// Must be at least 4.
 pub const M: usize = 8;
 
+// Node splitting relies on overflowing nodes having at least 4 children.
+const _: () = assert!(M >= 4, "the B-Tree order `M` must be at least 4");
+
+/// Raw B-Tree.
+///
+/// Items are ordered using the comparison functions given to each method,
+/// which must all be consistent with the same total order. In particular,
+/// `f64::partial_cmp` is not a total order because of `NaN`:
+/// [`total_f64_cmp`] and [`total_f32_cmp`] can be used instead.
+///
+/// Lookup methods compare items with a key of any type `Q`, which the
+/// caller extracts once. Each item met during the descent is compared at
+/// most once, so for expensive comparisons it is best to store the key
+/// inline in `T` and compare it directly with the key:
+///
+/// ```
+/// use raw_btree::RawBTree;
+///
+/// struct Entry {
+///     name: String,
+///     payload: [u8; 256],
+/// }
+///
+/// let cmp = |a: &Entry, b: &Entry| a.name.cmp(&b.name);
+/// let mut tree: RawBTree<Entry> = RawBTree::new();
+/// tree.insert(cmp, Entry { name: "a".into(), payload: [0; 256] });
+///
+/// let entry = tree.get(|e, name: &str| e.name.as_str().cmp(name), "a");
+/// assert!(entry.is_some());
+/// ```
 pub struct RawBTree<T, S: Storage<T> = BoxStorage> {
 	/// Allocated and free nodes.
 	nodes: S,
@@ -33,27 +99,295 @@ pub struct RawBTree<T, S: Storage<T> = BoxStorage> {
 	/// Number of items in the tree.
 	len: usize,
 
+	/// Rebalance observer.
+	observer: ObserverSlot<S::Node>,
+
 	item: PhantomData<T>,
 }
 
-impl<T, S: Storage<T>> Default for RawBTree<T, S> {
+impl<T, S: Storage<T> + Default> Default for RawBTree<T, S> {
 	fn default() -> Self {
 		Self::new()
 	}
 }
 
-impl<T, S: Storage<T>> RawBTree<T, S> {
+impl<T, S: Storage<T> + Default> RawBTree<T, S> {
 	/// Create a new empty B-tree.
 	#[inline]
 	pub fn new() -> RawBTree<T, S> {
+		Self::with_storage(S::default())
+	}
+
+	/// Creates a tree by applying the given log of operations in order.
+	///
+	/// Items are inserted using `cmp`, and removed using `key_cmp`. The
+	/// operations are consumed lazily. The resulting items only depend on the
+	/// log, and the tree is validated at the end in debug mode.
+	pub fn replay<Q>(
+		cmp: impl Fn(&T, &T) -> Ordering,
+		key_cmp: impl Fn(&T, &Q) -> Ordering,
+		ops: impl IntoIterator<Item = LogOp<T, Q>>,
+	) -> Self {
+		let mut result = Self::new();
+		for op in ops {
+			match op {
+				LogOp::Insert(item) => {
+					result.insert(&cmp, item);
+				}
+				LogOp::Remove(key) => {
+					result.remove(&key_cmp, &key);
+				}
+			}
+		}
+
+		#[cfg(debug_assertions)]
+		result.validate(&cmp);
+		result
+	}
+
+	/// Creates a tree whose leaves are given as groups of sorted items.
+	///
+	/// Each group becomes exactly one leaf, holding all its items. Leaves are
+	/// separated by the items of `separators`, the first separator going
+	/// between the first and second groups, and so on: there must be exactly
+	/// one separator less than groups. Each group must hold between `M / 2`
+	/// and `M` items, unless it is the only group. The internal levels are
+	/// built from the group boundaries.
+	///
+	/// Groups sizes, separators count and ordering are checked in debug mode.
+	pub fn from_leaf_groups(
+		cmp: impl Fn(&T, &T) -> Ordering,
+		groups: impl IntoIterator<Item = Vec<T>>,
+		separators: impl IntoIterator<Item = T>,
+	) -> Self {
+		let mut result = Self::new();
+		let mut groups = groups.into_iter().peekable();
+		let mut leaf_separators = separators.into_iter();
+
+		// Reserve the known leaves and their ancestors.
+		let mut level_len = groups.size_hint().0;
+		let mut node_count = level_len;
+		while level_len > 1 {
+			level_len = level_len.div_ceil(M);
+			node_count += level_len
+		}
+		result.nodes.reserve(node_count);
+
+		// Current level nodes, and the separators between them.
+		let mut nodes = Vec::new();
+		let mut separators = Vec::new();
+
+		while let Some(group) = groups.next() {
+			let is_last = groups.peek().is_none();
+			debug_assert!(
+				!group.is_empty()
+					&& group.len() <= M
+					&& ((nodes.is_empty() && is_last) || group.len() >= M / 2),
+				"invalid leaf group size"
+			);
+			debug_assert!(
+				group.windows(2).all(|w| cmp(&w[0], &w[1]).is_lt()),
+				"leaf group is not sorted"
+			);
+
+			if let Some(&previous_id) = nodes.last() {
+				let separator = leaf_separators.next().expect("missing leaf separator");
+				debug_assert!(
+					{
+						let previous = unsafe { result.nodes.get(previous_id) };
+						let last = previous.item((previous.item_count() - 1).into()).unwrap();
+						cmp(last, &separator).is_lt() && cmp(&separator, &group[0]).is_lt()
+					},
+					"leaf separator is not between its groups"
+				);
+
+				result.len += 1;
+				separators.push(separator)
+			}
+
+			result.len += group.len();
+			let leaf = Node::Leaf(node::LeafNode::new(None, group.into_iter().collect()));
+			nodes.push(result.nodes.allocate_node(leaf))
+		}
+
+		debug_assert!(leaf_separators.next().is_none(), "too many leaf separators");
+
+		while nodes.len() > 1 {
+			let n = nodes.len();
+			let parent_count = n.div_ceil(M);
+			let mut children = nodes.into_iter();
+			let mut child_separators = separators.into_iter();
+			nodes = Vec::with_capacity(parent_count);
+			separators = Vec::with_capacity(parent_count - 1);
+
+			for i in 0..parent_count {
+				let child_count = n / parent_count + usize::from(i < n % parent_count);
+				let first_child = children.next().unwrap();
+				let other_children = (1..child_count)
+					.map(|_| node::internal::Branch {
+						item: child_separators.next().unwrap(),
+						child: children.next().unwrap(),
+					})
+					.collect();
+
+				let internal = node::InternalNode::new(None, first_child, other_children);
+				nodes.push(unsafe { result.nodes.insert_node(Node::Internal(internal)) });
+
+				if i + 1 < parent_count {
+					separators.push(child_separators.next().unwrap())
+				}
+			}
+		}
+
+		result.root = nodes.pop();
+		result
+	}
+
+	/// Creates a tree from a vector of strictly sorted items.
+	///
+	/// Items are moved out of the vector, which is split into evenly sized
+	/// leaf groups and their separators, passed to
+	/// [`Self::from_leaf_groups`]. Items are not cloned, and the node storage
+	/// is reserved upfront.
+	///
+	/// Ordering is only checked in debug mode. If the items are not sorted,
+	/// the resulting tree is invalid. See [`Self::try_from_sorted_vec`] for a
+	/// checked variant.
+	pub fn from_sorted_vec(cmp: impl Fn(&T, &T) -> Ordering, items: Vec<T>) -> Self {
+		let n = items.len();
+		if n == 0 {
+			return Self::new();
+		}
+
+		// Every leaf but the last one is followed by a separator.
+		let group_count = (n + 1).div_ceil(M + 1);
+		let leaf_items = n + 1 - group_count;
+		let mut items = items.into_iter();
+		let mut groups = Vec::with_capacity(group_count);
+		let mut separators = Vec::with_capacity(group_count.saturating_sub(1));
+		for i in 0..group_count {
+			let group_len = leaf_items / group_count + usize::from(i < leaf_items % group_count);
+			groups.push(items.by_ref().take(group_len).collect::<Vec<_>>());
+			separators.extend(items.next())
+		}
+
+		Self::from_leaf_groups(cmp, groups, separators)
+	}
+
+	/// Creates a tree from a vector of items, checking that they are strictly
+	/// sorted.
+	///
+	/// Returns the vector untouched if it is not strictly sorted. Otherwise
+	/// behaves like [`Self::from_sorted_vec`].
+	pub fn try_from_sorted_vec(
+		cmp: impl Fn(&T, &T) -> Ordering,
+		items: Vec<T>,
+	) -> Result<Self, Vec<T>> {
+		if items.windows(2).all(|w| cmp(&w[0], &w[1]).is_lt()) {
+			Ok(Self::from_sorted_vec(cmp, items))
+		} else {
+			Err(items)
+		}
+	}
+}
+
+impl<T, S: Storage<T>> RawBTree<T, S> {
+	/// Create a new empty B-tree using the given node storage.
+	#[inline]
+	pub fn with_storage(nodes: S) -> RawBTree<T, S> {
 		RawBTree {
-			nodes: Default::default(),
+			nodes,
 			root: None,
 			len: 0,
+			observer: ObserverSlot::new(),
+			item: PhantomData,
+		}
+	}
+
+	/// Create a new empty B-tree, with a storage configured like the one of
+	/// `other` and able to hold as many nodes.
+	pub fn with_capacity_like(other: &Self) -> RawBTree<T, S> {
+		let mut nodes = other.nodes.empty();
+		nodes.reserve(other.node_count());
+		Self::with_storage(nodes)
+	}
+
+	/// Sets a callback called on every node split, rotation and merge
+	/// performed while rebalancing the tree.
+	///
+	/// The observer replaces any previously set observer. It is not cloned
+	/// along with the tree, and is dropped by [`Self::into_raw_parts`].
+	#[cfg(feature = "rebalance-observer")]
+	pub fn with_rebalance_observer(
+		&mut self,
+		observer: impl FnMut(RebalanceEvent<S::Node>) + Send + 'static,
+	) {
+		self.observer.set(observer)
+	}
+
+	/// Sets a rebalancing observer accumulating the number of splits, merges
+	/// and rotations performed on the tree.
+	///
+	/// Returns the shared counters, that can be read at any time. This
+	/// replaces any previously set observer.
+	#[cfg(feature = "rebalance-observer")]
+	pub fn with_rebalance_counters(&mut self) -> Arc<Mutex<RebalanceCounters>> {
+		let counters = Arc::new(Mutex::new(RebalanceCounters::default()));
+		let observed = counters.clone();
+		self.with_rebalance_observer(move |event| observed.lock().unwrap().record(&event));
+		counters
+	}
+
+	/// Removes the rebalancing observer, if any.
+	#[cfg(feature = "rebalance-observer")]
+	pub fn clear_rebalance_observer(&mut self) {
+		self.observer.clear()
+	}
+
+	/// Creates a tree from its raw parts.
+	///
+	/// This is the inverse of [`Self::into_raw_parts`].
+	///
+	/// # Safety
+	///
+	/// - `root` must be the root node of a valid B-Tree in `nodes`, or `None`
+	///   if the tree is empty.
+	/// - `len` must be the number of items in the tree.
+	#[inline]
+	pub unsafe fn from_raw_parts(nodes: S, root: Option<S::Node>, len: usize) -> Self {
+		RawBTree {
+			nodes,
+			root,
+			len,
+			observer: ObserverSlot::new(),
 			item: PhantomData,
 		}
 	}
 
+	/// Decomposes the tree into its raw parts: the node storage, the root
+	/// node identifier and the number of items.
+	///
+	/// Nothing is dropped. The tree can be rebuilt using
+	/// [`Self::from_raw_parts`].
+	#[inline]
+	pub fn into_raw_parts(self) -> (S, Option<S::Node>, usize) {
+		let mut this = std::mem::ManuallyDrop::new(self);
+		let nodes = unsafe {
+			std::ptr::drop_in_place(&mut this.observer);
+			std::ptr::read(&this.nodes)
+		};
+		(nodes, this.root, this.len)
+	}
+
+	/// Returns the identifier of the root node, or `None` if the tree is
+	/// empty.
+	///
+	/// The identifier is invalidated by any mutation of the tree.
+	#[inline]
+	pub fn root(&self) -> Option<S::Node> {
+		self.root
+	}
+
 	#[inline]
 	pub fn is_empty(&self) -> bool {
 		self.root.is_none()
@@ -64,6 +398,132 @@ impl<T, S: Storage<T>> RawBTree<T, S> {
 		self.len
 	}
 
+	/// Returns the number of levels in the tree.
+	///
+	/// Returns `0` if the tree is empty.
+	pub fn height(&self) -> usize {
+		let mut height = 0;
+		let mut id = self.root;
+		while let Some(current) = id {
+			height += 1;
+			id = unsafe { self.nodes.get(current).child_id_opt(0) };
+		}
+
+		height
+	}
+
+	/// Returns the depth of the deepest node shared by the search paths of
+	/// the two given keys, the root node being at depth `0`.
+	///
+	/// Both searches are performed at once, and the depth at which they take
+	/// different branches, or one of them finds its key in an internal node,
+	/// is returned. If both searches end in the same leaf, whether they find
+	/// their key or not, the [height](Self::height) of the tree is returned.
+	/// Returns `0` if the tree is empty.
+	pub fn common_depth<Q: ?Sized>(&self, cmp: impl Fn(&T, &Q) -> Ordering, a: &Q, b: &Q) -> usize {
+		let mut depth = 0;
+		let mut id = self.root;
+		while let Some(current) = id {
+			let node = unsafe { self.nodes.get(current) };
+			if node.is_leaf() {
+				return depth + 1;
+			}
+
+			match (node.offset_of(&cmp, a), node.offset_of(&cmp, b)) {
+				(Err((i, Some(child_id))), Err((j, _))) if i == j => {
+					depth += 1;
+					id = Some(child_id)
+				}
+				_ => break,
+			}
+		}
+
+		depth
+	}
+
+	/// Returns the number of nodes in the tree.
+	pub fn node_count(&self) -> usize {
+		let mut count = 0;
+		self.visit_from_leaves(|_| count += 1);
+		count
+	}
+
+	/// Computes the tree statistics in a single traversal.
+	pub fn stats(&self) -> TreeStats {
+		let mut stats = TreeStats {
+			len: self.len,
+			height: self.height(),
+			..Default::default()
+		};
+
+		self.visit_from_leaves(|id| {
+			let node = unsafe { self.nodes.get(id) };
+			let item_count = node.item_count();
+
+			stats.node_count += 1;
+			if node.is_leaf() {
+				stats.leaf_count += 1
+			} else {
+				stats.internal_count += 1
+			}
+
+			stats.min_occupancy = Some(
+				stats
+					.min_occupancy
+					.map_or(item_count, |m| m.min(item_count)),
+			);
+			stats.max_occupancy = Some(
+				stats
+					.max_occupancy
+					.map_or(item_count, |m| m.max(item_count)),
+			);
+		});
+
+		if stats.node_count > 0 {
+			// Every item is held by exactly one node.
+			stats.avg_occupancy = Some(self.len as f64 / stats.node_count as f64)
+		}
+
+		stats
+	}
+
+	/// Measures how scattered the nodes visited by an in-order scan are.
+	///
+	/// The address of a node is given by its identifier converted into
+	/// `usize`: the pointer value for [`BoxStorage`], the slot index for
+	/// [`CowStorage`]. Few backward transitions and a low average distance
+	/// denote a cache-friendly layout.
+	pub fn scan_locality(&self) -> LocalityStats
+	where
+		S::Node: Into<usize>,
+	{
+		let mut stats = LocalityStats::default();
+		let mut total_distance = 0;
+		let mut previous: Option<usize> = None;
+
+		for (id, _) in self.iter_with_node() {
+			let addr = id.into();
+			if let Some(previous) = previous.filter(|p| *p != addr) {
+				stats.transitions += 1;
+				if addr > previous {
+					stats.forward += 1
+				} else {
+					stats.backward += 1
+				}
+
+				total_distance += addr.abs_diff(previous);
+			}
+
+			previous = Some(addr)
+		}
+
+		if stats.transitions > 0 {
+			stats.avg_distance = Some(total_distance as f64 / stats.transitions as f64)
+		}
+
+		stats
+	}
+
 	pub fn address_of<Q: ?Sized>(
 		&self,
 		cmp: impl Fn(&T, &Q) -> Ordering,
@@ -75,6 +535,136 @@ impl<T, S: Storage<T>> RawBTree<T, S> {
 		}
 	}
 
+	/// Returns the range going from the address of the first item equal to
+	/// `key` to the address just past the last one.
+	///
+	/// The range is empty if no item matches the key. The end address
+	/// may be a back address. Returns `None` if the tree is empty.
+	pub fn equal_range<Q: ?Sized>(
+		&self,
+		cmp: impl Fn(&T, &Q) -> Ordering,
+		key: &Q,
+	) -> Option<std::ops::Range<Address<S::Node>>> {
+		match self.address_of(&cmp, key) {
+			Ok(addr) => unsafe {
+				let is_equal = |a: Address<S::Node>| cmp(self.get_at(a).unwrap(), key).is_eq();
+
+				let mut begin = addr;
+				while let Some(previous) = self.nodes.previous_item_address(begin) {
+					if !is_equal(previous) {
+						break;
+					}
+
+					begin = previous
+				}
+
+				let mut last = addr;
+				while let Some(next) = self.nodes.next_item_address(last) {
+					if !is_equal(next) {
+						break;
+					}
+
+					last = next
+				}
+
+				let end = self.nodes.next_item_or_back_address(last).unwrap();
+				Some(begin..end)
+			},
+			Err(addr) => addr.map(|addr| addr..addr),
+		}
+	}
+
+	/// Returns the node in which an item matching the given key would be
+	/// inserted.
+	///
+	/// If an item already matches the key, its node is returned. Otherwise,
+	/// this is the leaf node where the item would be inserted. Returns `None`
+	/// if the tree is empty.
+	pub fn insertion_node<Q: ?Sized>(
+		&self,
+		cmp: impl Fn(&T, &Q) -> Ordering,
+		key: &Q,
+	) -> Option<S::Node> {
+		match self.address_of(cmp, key) {
+			Ok(addr) => Some(addr.node),
+			Err(addr) => addr.map(|addr| addr.node),
+		}
+	}
+
+	/// Returns the address of the first item not below the given bound.
+	pub fn lower_bound_address<Q: ?Sized>(
+		&self,
+		cmp: impl Fn(&T, &Q) -> Ordering,
+		bound: Bound<&Q>,
+	) -> Option<Address<S::Node>> {
+		match bound {
+			Bound::Unbounded => self.first_item_address(),
+			Bound::Included(key) => match self.address_of(cmp, key) {
+				Ok(addr) => Some(addr),
+				Err(addr) => addr.and_then(|addr| unsafe { self.nodes.normalize(addr) }),
+			},
+			Bound::Excluded(key) => match self.address_of(cmp, key) {
+				Ok(addr) => unsafe { self.nodes.next_item_address(addr) },
+				Err(addr) => addr.and_then(|addr| unsafe { self.nodes.normalize(addr) }),
+			},
+		}
+	}
+
+	/// Find the address of the given key, avoiding the comparison of
+	/// prefixes known to be shared with the key.
+	///
+	/// This is a variant of [`Self::address_of`] for keys that are
+	/// lexicographically compared sequences (such as strings), where
+	/// comparisons are expensive. During the descent, the length of the common
+	/// prefix between the key and the closest lower and upper bounds found so
+	/// far is remembered. Every item between those bounds shares at least the
+	/// shortest of those prefixes with the key, so they don't need to be
+	/// compared again, neither in the current node nor in its descendants.
+	///
+	/// `cmp(item, key, skip)` must compare `item` with `key`, knowing that
+	/// their first `skip` elements are equal. It returns the ordering along
+	/// with the length of the common prefix between `item` and `key`.
+	pub fn address_of_cached<Q: ?Sized>(
+		&self,
+		cmp: impl Fn(&T, &Q, usize) -> (Ordering, usize),
+		key: &Q,
+	) -> Result<Address<S::Node>, Option<Address<S::Node>>> {
+		let mut id = self.root.ok_or(None)?;
+		let mut lower_prefix = 0;
+		let mut upper_prefix = 0;
+
+		loop {
+			let node = unsafe { self.nodes.get(id) };
+			let mut i = 0;
+			let mut j = node.item_count();
+
+			// invariants:
+			// items before `i` are lower than the key,
+			// items from `j` are greater than the key.
+			while i < j {
+				let k = (i + j) / 2;
+				let item = node.item(k.into()).unwrap();
+				let (ord, prefix) = cmp(item, key, lower_prefix.min(upper_prefix));
+				match ord {
+					Ordering::Equal => return Ok(Address::new(id, k.into())),
+					Ordering::Less => {
+						i = k + 1;
+						lower_prefix = prefix
+					}
+					Ordering::Greater => {
+						j = k;
+						upper_prefix = prefix
+					}
+				}
+			}
+
+			match node.child_id_opt(i) {
+				Some(child_id) => id = child_id,
+				None => return Err(Some(Address::new(id, i.into()))),
+			}
+		}
+	}
+
 	pub fn first_item_address(&self) -> Option<Address<S::Node>> {
 		self.root.map(|mut id| unsafe {
 			loop {
@@ -83,144 +673,1968 @@ impl<T, S: Storage<T>> RawBTree<T, S> {
 					None => break Address::new(id, 0.into()),
 				}
 			}
-		})
-	}
+		})
+	}
+
+	// fn first_back_address(&self) -> Address {
+	// 	match self.root {
+	// 		Some(mut id) => loop {
+	// 			match self.node(id).child_id_opt(0) {
+	// 				Some(child_id) => id = child_id,
+	// 				None => return Address::new(id, 0.into()), // TODO FIXME thechnically not the first
+	// 			}
+	// 		},
+	// 		None => Address::nowhere(),
+	// 	}
+	// }
+
+	fn last_item_address(&self) -> Option<Address<S::Node>> {
+		self.root.map(|mut id| unsafe {
+			loop {
+				let node = self.nodes.get(id);
+				let index = node.item_count();
+				match node.child_id_opt(index) {
+					Some(child_id) => id = child_id,
+					None => break Address::new(id, (index - 1).into()),
+				}
+			}
+		})
+	}
+
+	// fn last_valid_address(&self) -> Address {
+	// 	match self.root {
+	// 		Some(mut id) => loop {
+	// 			let node = self.node(id);
+	// 			let index = node.item_count();
+	// 			match node.child_id_opt(index) {
+	// 				Some(child_id) => id = child_id,
+	// 				None => return Address::new(id, index.into()),
+	// 			}
+	// 		},
+	// 		None => Address::nowhere(),
+	// 	}
+	// }
+
+	/// Return the item at the given address.
+	///
+	/// # Safety
+	///
+	/// The address's node must not have been deallocated.
+	#[inline]
+	pub unsafe fn get_at(&self, addr: Address<S::Node>) -> Option<&T> {
+		self.nodes.get(addr.node).item(addr.offset)
+	}
+
+	/// Returns a mutable reference to the item at the given address.
+	///
+	/// # Safety
+	///
+	/// The address's node must not have been deallocated.
+	#[inline]
+	pub unsafe fn get_mut_at(&mut self, addr: Address<S::Node>) -> Option<&mut T> {
+		self.nodes.get_mut(addr.node).item_mut(addr.offset)
+	}
+
+	/// Returns the item address or back address directly following the given
+	/// address.
+	///
+	/// If there is an item after `addr` in the tree, its address is returned.
+	/// Otherwise, the back address directly following `addr` in its node is
+	/// returned, which can be used to insert a new item after `addr`. This is
+	/// the address used by removal operations to continue after removing an
+	/// item from an internal node.
+	///
+	/// # Safety
+	///
+	/// The address's node must not have been deallocated.
+	#[inline]
+	pub unsafe fn next_item_or_back_address(
+		&self,
+		addr: Address<S::Node>,
+	) -> Option<Address<S::Node>> {
+		self.nodes.next_item_or_back_address(addr)
+	}
+
+	/// Replaces the item at the given address, returning the old item.
+	///
+	/// If the address doesn't point to an item (for instance if it is a back
+	/// address), the new item is given back as `Err(item)`.
+	///
+	/// # Safety
+	///
+	/// The address's node must not have been deallocated.
+	#[inline]
+	pub unsafe fn replace_at_checked(&mut self, addr: Address<S::Node>, item: T) -> Result<T, T> {
+		match self.get_mut_at(addr) {
+			Some(old) => Ok(std::mem::replace(old, item)),
+			None => Err(item),
+		}
+	}
+
+	/// Updates the item at the given address in place.
+	///
+	/// `f` must not change the position of the item in the tree order.
+	/// Returns `false`, without calling `f`, if the address doesn't point to
+	/// an item.
+	///
+	/// # Safety
+	///
+	/// The address's node must not have been deallocated.
+	#[inline]
+	pub unsafe fn update_at(&mut self, addr: Address<S::Node>, f: impl FnOnce(&mut T)) -> bool {
+		match self.get_mut_at(addr) {
+			Some(item) => {
+				f(item);
+				true
+			}
+			None => false,
+		}
+	}
+
+	/// Swaps the items at the given addresses, without structural changes.
+	///
+	/// In debug mode, panics if `a` and `b` are equal or if the swapped
+	/// items are not ordered with their neighbors according to `cmp`.
+	///
+	/// # Safety
+	///
+	/// - Both addresses must point to items of the tree.
+	/// - The swap must preserve the order of the items.
+	pub unsafe fn swap_items(
+		&mut self,
+		cmp: impl Fn(&T, &T) -> Ordering,
+		a: Address<S::Node>,
+		b: Address<S::Node>,
+	) {
+		debug_assert!(a != b, "cannot swap an item with itself");
+		if a != b {
+			if a.node == b.node {
+				// Borrow the node only once.
+				self.nodes.get_mut(a.node).swap_items(a.offset, b.offset)
+			} else {
+				let a_item: *mut T = self.get_mut_at(a).unwrap();
+				let b_item: *mut T = self.get_mut_at(b).unwrap();
+				std::ptr::swap(a_item, b_item);
+			}
+
+			debug_assert!(self.is_ordered_at(&cmp, a) && self.is_ordered_at(&cmp, b))
+		}
+	}
+
+	/// Checks that the item at the given address is ordered with its
+	/// neighbors.
+	unsafe fn is_ordered_at(
+		&self,
+		cmp: impl Fn(&T, &T) -> Ordering,
+		addr: Address<S::Node>,
+	) -> bool {
+		let item = self.get_at(addr).unwrap();
+		let previous = self.nodes.previous_item_address(addr);
+		let next = self.nodes.next_item_address(addr);
+		previous.is_none_or(|p| cmp(self.get_at(p).unwrap(), item).is_lt())
+			&& next.is_none_or(|n| cmp(item, self.get_at(n).unwrap()).is_lt())
+	}
+
+	/// Returns the depth of the given node, the root node being at depth `0`.
+	///
+	/// # Safety
+	///
+	/// The node must not have been deallocated.
+	#[inline]
+	pub unsafe fn node_depth(&self, mut id: S::Node) -> usize {
+		let mut depth = 0;
+		while let Some(parent_id) = self.nodes.get(id).parent() {
+			depth += 1;
+			id = parent_id
+		}
+
+		depth
+	}
+
+	/// Checks if the given address points into a leaf node.
+	///
+	/// # Safety
+	///
+	/// The address's node must not have been deallocated.
+	#[inline]
+	pub unsafe fn is_leaf_address(&self, addr: Address<S::Node>) -> bool {
+		self.nodes.get(addr.node).is_leaf()
+	}
+
+	/// Creates a valid address from a node identifier and an offset.
+	///
+	/// Returns `None` if the offset is greater than the number of items in
+	/// the node. See [`Address`] for the definition of valid addresses.
+	///
+	/// # Safety
+	///
+	/// The node must not have been deallocated.
+	#[inline]
+	pub unsafe fn address(&self, node: S::Node, offset: usize) -> Option<Address<S::Node>> {
+		if offset <= self.nodes.get(node).item_count() {
+			Some(Address::new(node, offset.into()))
+		} else {
+			None
+		}
+	}
+
+	/// Returns the addresses of the items of the given node, in node-local
+	/// order.
+	///
+	/// # Safety
+	///
+	/// The node must not have been deallocated.
+	#[inline]
+	pub unsafe fn node_addresses(&self, id: S::Node) -> impl Iterator<Item = Address<S::Node>> {
+		let item_count = self.nodes.get(id).item_count();
+		(0..item_count).map(move |offset| Address::new(id, offset.into()))
+	}
+
+	/// Returns a read-only view of the subtree rooted in the given node.
+	///
+	/// # Safety
+	///
+	/// The node must not have been deallocated.
+	#[inline]
+	pub unsafe fn subtree(&self, id: S::Node) -> SubtreeView<'_, T, S> {
+		SubtreeView::new(self, id)
+	}
+
+	#[inline]
+	pub fn get<Q: ?Sized>(&self, cmp: impl Fn(&T, &Q) -> Ordering, key: &Q) -> Option<&T> {
+		self.address_of(cmp, key)
+			.ok()
+			.and_then(|addr| unsafe { self.get_at(addr) })
+	}
+
+	/// Returns the items matching each of the given keys.
+	///
+	/// Keys are expected to be sorted. Instead of starting each search from
+	/// the root, the search for a key starts from the lowest ancestor of the
+	/// node where the previous search ended that may contain the key. This is
+	/// much faster than independent lookups when keys are close to each
+	/// other.
+	///
+	/// A key lower than the item found (or the last item preceding the
+	/// position searched) for the previous key is looked up independently
+	/// with [`Self::get`], so keys in any order still give correct results.
+	pub fn get_many<'a, Q>(
+		&'a self,
+		cmp: impl Fn(&T, &Q) -> Ordering,
+		keys: &[Q],
+	) -> Vec<Option<&'a T>> {
+		let mut result = Vec::with_capacity(keys.len());
+		let mut id = self.root;
+
+		// Item matching the previous key, or the last item preceding it.
+		let mut last: Option<&T> = None;
+
+		for key in keys {
+			if last.is_some_and(|item| cmp(item, key).is_gt()) {
+				result.push(self.get(&cmp, key));
+				continue;
+			}
+
+			match id {
+				Some(current) => unsafe {
+					let start = self.lowest_ancestor_containing(&cmp, current, key);
+					let item = match self.nodes.address_in(start, &cmp, key) {
+						Ok(addr) => {
+							id = Some(addr.node);
+							last = self.get_at(addr);
+							last
+						}
+						Err(addr) => {
+							id = Some(addr.node);
+							last = self
+								.nodes
+								.previous_item_address(addr)
+								.and_then(|addr| self.get_at(addr));
+							None
+						}
+					};
+
+					result.push(item)
+				},
+				None => result.push(None),
+			}
+		}
+
+		result
+	}
+
+	/// Returns the lowest ancestor of the given node (included) whose subtree
+	/// may contain the given key.
+	///
+	/// # Safety
+	///
+	/// The node must not have been deallocated.
+	unsafe fn lowest_ancestor_containing<Q: ?Sized>(
+		&self,
+		cmp: impl Fn(&T, &Q) -> Ordering,
+		mut id: S::Node,
+		key: &Q,
+	) -> S::Node {
+		// Lowest node not known to exclude the key.
+		let mut candidate = id;
+
+		// Whether the candidate lower and upper separators have been found
+		// (and are known not to exclude the key).
+		let mut lower = false;
+		let mut upper = false;
+
+		while !(lower && upper) {
+			let parent_id = match self.nodes.get(id).parent() {
+				Some(parent_id) => parent_id,
+				None => break,
+			};
+
+			let parent = self.nodes.get(parent_id);
+			let index = parent.child_index(id).unwrap();
+			let mut excluded = false;
+
+			if !lower && index > 0 {
+				lower = true;
+				excluded |= cmp(parent.item((index - 1).into()).unwrap(), key).is_ge()
+			}
+
+			if !upper && index < parent.item_count() {
+				upper = true;
+				excluded |= cmp(parent.item(index.into()).unwrap(), key).is_le()
+			}
+
+			if excluded {
+				candidate = parent_id;
+				lower = false;
+				upper = false;
+			}
+
+			id = parent_id
+		}
+
+		candidate
+	}
+
+	#[inline]
+	pub fn get_mut<Q: ?Sized>(
+		&mut self,
+		cmp: impl Fn(&T, &Q) -> Ordering,
+		key: &Q,
+	) -> Option<&mut T> {
+		self.address_of(cmp, key)
+			.ok()
+			.and_then(|addr| unsafe { self.get_mut_at(addr) })
+	}
+
+	#[inline]
+	pub fn first(&self) -> Option<&T> {
+		self.first_item_address()
+			.and_then(|addr| unsafe { self.get_at(addr) })
+	}
+
+	#[inline]
+	pub fn first_mut(&mut self) -> Option<&mut T> {
+		self.first_item_address()
+			.and_then(|addr| unsafe { self.get_mut_at(addr) })
+	}
+
+	#[inline]
+	pub fn last(&self) -> Option<&T> {
+		self.last_item_address()
+			.and_then(|addr| unsafe { self.get_at(addr) })
+	}
+
+	#[inline]
+	pub fn last_mut(&mut self) -> Option<&mut T> {
+		self.last_item_address()
+			.and_then(|addr| unsafe { self.get_mut_at(addr) })
+	}
+
+	/// Returns both the first and last items of the tree.
+	///
+	/// Returns `None` if the tree is empty. If the tree has a single item, it
+	/// is returned twice.
+	pub fn bounds(&self) -> Option<(&T, &T)> {
+		self.first().zip(self.last())
+	}
+
+	/// Returns the keys of the first and last items of the tree.
+	///
+	/// The key of an item is given by `key_of`. Returns `None` if the tree is
+	/// empty. If the tree has a single item, its key is returned twice.
+	pub fn key_span<K: ?Sized>(&self, key_of: impl Fn(&T) -> &K) -> Option<(&K, &K)> {
+		self.bounds()
+			.map(|(first, last)| (key_of(first), key_of(last)))
+	}
+
+	/// Returns the item at the given index, or the last item if the index is
+	/// out of bounds, along with its actual index.
+	///
+	/// Returns `None` if the tree is empty.
+	///
+	/// This walks the tree in order up to the returned item, which makes it
+	/// linear in the index.
+	// TODO use subtree item counts to find the item in logarithmic time.
+	pub fn item_at_or_before_index(&self, approx: usize) -> Option<(usize, &T)> {
+		let index = approx.min(self.len.checked_sub(1)?);
+		self.iter().nth(index).map(|item| (index, item))
+	}
+
+	/// Returns the number of items located before the given address, which
+	/// is the index of the item at this address.
+	///
+	/// This walks the items backward from the address, in O(index).
+	///
+	/// # Safety
+	///
+	/// The address's node must not have been deallocated.
+	pub unsafe fn index_of_address(&self, addr: Address<S::Node>) -> usize {
+		let mut index = 0;
+		let mut current = addr;
+		while let Some(previous) = self.nodes.previous_item_address(current) {
+			index += 1;
+			current = previous
+		}
+
+		index
+	}
+
+	/// Returns the `k`-th smallest item, if any.
+	///
+	/// Without per-subtree item counts, this walks the item addresses from
+	/// the first item, in O(k), only dereferencing the selected item.
+	///
+	/// Once internal nodes store the number of items of each of their
+	/// subtrees, this can descend from the root instead, skipping whole
+	/// subtrees (and their separator) while `k` exceeds their item count, in
+	/// O(log N). The signature will stay the same.
+	pub fn select_approx(&self, k: usize) -> Option<&T> {
+		if k >= self.len {
+			return None;
+		}
+
+		let mut addr = self.first_item_address()?;
+		for _ in 0..k {
+			addr = unsafe { self.nodes.next_item_address(addr)? }
+		}
+
+		unsafe { self.get_at(addr) }
+	}
+
+	/// Reserves the insertion point of the given key.
+	///
+	/// If an item already matches the key, its entry is returned instead.
+	pub fn reserve_insertion<Q: ?Sized>(
+		&mut self,
+		cmp: impl Fn(&T, &Q) -> Ordering,
+		key: &Q,
+	) -> Result<ReservedSlot<'_, T, S>, OccupiedEntry<'_, T, S>> {
+		match self.address_of(cmp, key) {
+			Ok(addr) => Err(unsafe { OccupiedEntry::new(self, addr) }),
+			Err(addr) => Ok(unsafe { ReservedSlot::new(self, addr) }),
+		}
+	}
+
+	/// Returns the entry of the first item of the tree, if any.
+	#[inline]
+	pub fn first_entry(&mut self) -> Option<OccupiedEntry<'_, T, S>> {
+		self.first_item_address()
+			.map(|addr| unsafe { OccupiedEntry::new(self, addr) })
+	}
+
+	/// Returns the entry of the last item of the tree, if any.
+	#[inline]
+	pub fn last_entry(&mut self) -> Option<OccupiedEntry<'_, T, S>> {
+		self.last_item_address()
+			.map(|addr| unsafe { OccupiedEntry::new(self, addr) })
+	}
+
+	/// Remove the first item of the tree and return it.
+	#[inline]
+	pub fn pop_first(&mut self) -> Option<T> {
+		self.first_entry().map(OccupiedEntry::remove)
+	}
+
+	/// Remove the last item of the tree and return it.
+	#[inline]
+	pub fn pop_last(&mut self) -> Option<T> {
+		self.last_entry().map(OccupiedEntry::remove)
+	}
+
+	/// Returns the first item of the tree.
+	///
+	/// Alias for [`Self::first`], for deque-style use. Note that the position
+	/// of inserted items is still determined by the comparison function.
+	#[inline]
+	pub fn front(&self) -> Option<&T> {
+		self.first()
+	}
+
+	/// Returns the last item of the tree.
+	///
+	/// Alias for [`Self::last`], for deque-style use. Note that the position
+	/// of inserted items is still determined by the comparison function.
+	#[inline]
+	pub fn back(&self) -> Option<&T> {
+		self.last()
+	}
+
+	/// Remove the first item of the tree and return it.
+	///
+	/// Alias for [`Self::pop_first`], for deque-style use.
+	#[inline]
+	pub fn pop_front(&mut self) -> Option<T> {
+		self.pop_first()
+	}
+
+	/// Remove the last item of the tree and return it.
+	///
+	/// Alias for [`Self::pop_last`], for deque-style use.
+	#[inline]
+	pub fn pop_back(&mut self) -> Option<T> {
+		self.pop_last()
+	}
+
+	/// Moves the greatest half of the items into a new tree, and returns it.
+	///
+	/// If the tree has an odd number of items, the returned tree holds one
+	/// item less than this one. The new tree uses a storage configured like
+	/// the one of this tree.
+	///
+	/// The median item is found by walking the leaves, and the tree is then
+	/// split structurally at this item: whole subtrees are moved to the new
+	/// tree, and only the nodes along the split path are rebalanced.
+	pub fn split_balanced(&mut self) -> RawBTree<T, S> {
+		let index = self.len - self.len / 2;
+		match self.address_at_index(index) {
+			Some(addr) => unsafe { self.split_off_at(addr, index) },
+			None => Self::with_storage(self.nodes.empty()),
+		}
+	}
+
+	/// Returns the address of the item at the given index, if any.
+	///
+	/// Leaves are walked in order, skipping their items at once, which makes
+	/// it linear in the number of leaves preceding the item.
+	fn address_at_index(&self, mut index: usize) -> Option<Address<S::Node>> {
+		let mut addr = self.first_item_address()?;
+		unsafe {
+			loop {
+				let item_count = self.nodes.get(addr.node).item_count();
+				if index < item_count {
+					break Some(Address::new(addr.node, index.into()));
+				}
+
+				// Skip the leaf, then its following separator.
+				index -= item_count;
+				let separator = self
+					.nodes
+					.next_item_address(Address::new(addr.node, (item_count - 1).into()))?;
+				if index == 0 {
+					break Some(separator);
+				}
+
+				index -= 1;
+				addr = self.nodes.next_item_address(separator)?
+			}
+		}
+	}
+
+	/// Moves the items from the given address (included) into a new tree,
+	/// and returns it.
+	///
+	/// The `index` is the index of the addressed item in the tree.
+	///
+	/// The nodes along the path to the address are cut in two, and the
+	/// subtrees on the right of the path are moved to the new tree. The
+	/// borders of both trees are then rebalanced.
+	///
+	/// # Safety
+	///
+	/// The address must point to the item of the tree at the given index.
+	unsafe fn split_off_at(&mut self, addr: Address<S::Node>, index: usize) -> Self {
+		let mut result = Self::with_storage(self.nodes.empty());
+		let addr = self.nodes.leaf_address(addr);
+
+		// Cut the path from the leaf up to the root, building the right
+		// side in the new storage.
+		let right = self
+			.nodes
+			.get_mut(addr.node)
+			.split_off(addr.offset.unwrap(), None);
+		let mut right_id = result.nodes.allocate_node(right);
+		let mut id = addr.node;
+		while let Some(parent_id) = self.nodes.get(id).parent() {
+			let offset = self.nodes.get(parent_id).child_index(id).unwrap();
+			let mut right = self
+				.nodes
+				.get_mut(parent_id)
+				.split_off(offset, Some(right_id));
+			for child_id in right.children_mut().skip(1) {
+				*child_id = Self::move_subtree(&mut self.nodes, &mut result.nodes, *child_id)
+			}
+
+			right_id = result.nodes.insert_node(right);
+			id = parent_id
+		}
+
+		let observer = self.observer.get();
+		self.root = balancing::fix_border(&mut self.nodes, self.root, Border::Right, observer);
+		result.root = balancing::fix_border(&mut result.nodes, Some(right_id), Border::Left, None);
+		result.len = self.len - index;
+		self.len = index;
+		result
+	}
+
+	/// Moves the given subtree from the `source` storage to the `target`
+	/// storage, and returns its new root.
+	///
+	/// # Safety
+	///
+	/// The node must not have been deallocated from the `source` storage.
+	unsafe fn move_subtree(source: &mut S, target: &mut S, id: S::Node) -> S::Node {
+		let mut node = source.release_node(id);
+		for child_id in node.children_mut() {
+			*child_id = Self::move_subtree(source, target, *child_id)
+		}
+
+		target.insert_node(node)
+	}
+
+	/// Removes every item but the first `n` ones.
+	///
+	/// Does nothing if the tree has no more than `n` items.
+	// TODO split the tree at the `n`th item and drop the right part at once.
+	pub fn truncate(&mut self, n: usize) {
+		while self.len > n {
+			self.pop_last();
+		}
+	}
+
+	pub fn iter(&self) -> Iter<'_, T, S> {
+		Iter::new(self)
+	}
+
+	/// Returns an iterator over the items along with their index in the tree.
+	///
+	/// The index of an item is its position in order, which is also its
+	/// index for [`Self::select_approx`] and [`Self::index_of_address`],
+	/// including when iterating backward.
+	#[inline]
+	pub fn iter_enumerated(&self) -> std::iter::Enumerate<Iter<'_, T, S>> {
+		self.iter().enumerate()
+	}
+
+	/// Returns an iterator over the items between the given addresses.
+	///
+	/// The `start` address is inclusive, `None` meaning the first item.
+	/// The `end` address is exclusive, `None` meaning past the last item.
+	///
+	/// # Safety
+	///
+	/// Both addresses must be valid addresses in this tree, and `start` must
+	/// not be after `end`.
+	pub unsafe fn iter_between(
+		&self,
+		start: Option<Address<S::Node>>,
+		end: Option<Address<S::Node>>,
+	) -> Iter<'_, T, S> {
+		let addr = match start {
+			Some(addr) => self.nodes.normalize(addr),
+			None => self.first_item_address(),
+		};
+		let end = end.and_then(|addr| self.nodes.normalize(addr));
+
+		let mut len = 0;
+		let mut current = addr;
+		while let Some(a) = current {
+			if Some(a) == end {
+				break;
+			}
+
+			len += 1;
+			current = self.nodes.next_item_address(a);
+		}
+
+		Iter {
+			btree: self,
+			addr,
+			end,
+			len,
+		}
+	}
+
+	/// Returns an iterator over the items around the given center key.
+	///
+	/// Starting from the position of `center`, items are alternately taken
+	/// after and before it, for as long as `in_range` holds on each side.
+	/// The item matching `center`, if any, is returned first.
+	pub fn within<'a, Q: ?Sized>(
+		&'a self,
+		cmp: impl Fn(&T, &Q) -> Ordering,
+		center: &Q,
+		in_range: impl Fn(&T) -> bool + 'a,
+	) -> impl Iterator<Item = &'a T> {
+		let mut center_item = None;
+		let (mut after, mut before) = match self.address_of(cmp, center) {
+			Ok(addr) => unsafe {
+				center_item = self.get_at(addr).filter(|item| in_range(item));
+				(
+					self.nodes.next_item_address(addr),
+					self.nodes.previous_item_address(addr),
+				)
+			},
+			Err(Some(addr)) => unsafe {
+				(
+					self.nodes.normalize(addr),
+					self.nodes.previous_item_address(addr),
+				)
+			},
+			Err(None) => (None, None),
+		};
+
+		let mut forward = true;
+		center_item
+			.into_iter()
+			.chain(std::iter::from_fn(move || loop {
+				let is_after = match (after, before) {
+					(None, None) => return None,
+					(Some(_), None) => true,
+					(None, Some(_)) => false,
+					(Some(_), Some(_)) => forward,
+				};
+
+				let side = if is_after { &mut after } else { &mut before };
+				let addr = side.unwrap();
+				let item = unsafe { self.get_at(addr).unwrap() };
+				if in_range(item) {
+					*side = unsafe {
+						if is_after {
+							self.nodes.next_item_address(addr)
+						} else {
+							self.nodes.previous_item_address(addr)
+						}
+					};
+
+					forward = !is_after;
+					return Some(item);
+				} else {
+					*side = None
+				}
+			}))
+	}
+
+	/// Returns an iterator over successive chunks of `size` items, in order.
+	///
+	/// The last chunk may be shorter.
+	///
+	/// # Panics
+	///
+	/// Panics if `size` is zero.
+	pub fn chunks(&self, size: usize) -> impl Iterator<Item = std::iter::Take<Iter<'_, T, S>>> {
+		assert!(size != 0, "chunk size must be non-zero");
+		let mut items = self.iter();
+		std::iter::from_fn(move || {
+			if items.len() == 0 {
+				None
+			} else {
+				let chunk = items.take(size);
+				items.nth(size - 1);
+				Some(chunk)
+			}
+		})
+	}
+
+	/// Returns an iterator over the items whose key starts with the given
+	/// prefix, in order.
+	///
+	/// `cmp` must order the items according to their key.
+	pub fn range_prefix<Q: ?Sized + PrefixKey>(
+		&self,
+		cmp: impl Fn(&T, &Q) -> Ordering,
+		prefix: &Q,
+	) -> Iter<'_, T, S> {
+		match self.lower_bound_address(&cmp, Bound::Included(prefix)) {
+			Some(start) => {
+				let end = prefix.prefix_successor().and_then(|successor| {
+					self.lower_bound_address(&cmp, Bound::Included(successor.borrow()))
+				});
+
+				unsafe { self.iter_between(Some(start), end) }
+			}
+			None => Iter {
+				btree: self,
+				addr: None,
+				end: None,
+				len: 0,
+			},
+		}
+	}
+
+	/// Returns an iterator over the in-order merge of the tree items and the
+	/// given sorted slice.
+	///
+	/// When an item of the tree and an item of the slice compare equal, only
+	/// the item of the slice is returned.
+	pub fn iter_merged<'a>(
+		&'a self,
+		cmp: impl Fn(&T, &T) -> Ordering,
+		pending: &'a [T],
+	) -> impl Iterator<Item = &'a T> {
+		let mut items = self.iter().peekable();
+		let mut pending = pending.iter().peekable();
+		std::iter::from_fn(move || match (items.peek(), pending.peek()) {
+			(Some(a), Some(b)) => match cmp(a, b) {
+				Ordering::Less => items.next(),
+				Ordering::Greater => pending.next(),
+				Ordering::Equal => {
+					items.next();
+					pending.next()
+				}
+			},
+			(Some(_), None) => items.next(),
+			(None, _) => pending.next(),
+		})
+	}
+
+	/// Creates a new tree with a copy of the items of this tree that are not
+	/// in the `other` tree.
+	///
+	/// See [`Self::iter_excluding`]. The new tree uses a storage configured
+	/// like the one of this tree.
+	pub fn difference_tree(&self, cmp: impl Fn(&T, &T) -> Ordering, other: &Self) -> Self
+	where
+		T: Clone,
+	{
+		let mut result = Self::with_storage(self.nodes.empty());
+		for item in self.iter_excluding(&cmp, other) {
+			result.push_back(&cmp, item.clone());
+		}
+
+		result
+	}
+
+	/// Checks if every item of `other` is also in this tree.
+	///
+	/// Both trees are walked together, stopping at the first item of `other`
+	/// missing from this tree.
+	pub fn contains_all(&self, cmp: impl Fn(&T, &T) -> Ordering, other: &Self) -> bool {
+		if other.len > self.len {
+			return false;
+		}
+
+		let mut items = self.iter().peekable();
+		other.iter().all(|b| {
+			while items.next_if(|a| cmp(a, b).is_lt()).is_some() {}
+			items.next_if(|a| cmp(a, b).is_eq()).is_some()
+		})
+	}
+
+	/// Returns an iterator over the items of this tree that are not in the
+	/// `excluded` tree, in order.
+	///
+	/// Both trees are walked together, in a single pass.
+	pub fn iter_excluding<'a>(
+		&'a self,
+		cmp: impl Fn(&T, &T) -> Ordering + 'a,
+		excluded: &'a Self,
+	) -> impl Iterator<Item = &'a T> {
+		let mut excluded = excluded.iter().peekable();
+		self.iter().filter(move |item| {
+			while excluded.next_if(|e| cmp(e, item).is_lt()).is_some() {}
+			excluded.peek().is_none_or(|e| cmp(e, item).is_gt())
+		})
+	}
+
+	/// Returns an iterator over the items along with the depth of the node
+	/// holding them, the root node being at depth `0`.
+	pub fn iter_with_depth(&self) -> impl Iterator<Item = (usize, &T)> {
+		let mut addr = self.first_item_address();
+		std::iter::from_fn(move || unsafe {
+			let current = addr?;
+			addr = self.nodes.next_item_address(current);
+			Some((self.node_depth(current.node), self.get_at(current).unwrap()))
+		})
+	}
+
+	/// Returns an iterator over the items along with the node holding them.
+	pub fn iter_with_node(&self) -> impl Iterator<Item = (S::Node, &T)> {
+		let mut addr = self.first_item_address();
+		std::iter::from_fn(move || unsafe {
+			let current = addr?;
+			addr = self.nodes.next_item_address(current);
+			Some((current.node, self.get_at(current).unwrap()))
+		})
+	}
+
+	/// Returns an iterator over the keys of the first and last items of each
+	/// leaf, in order.
+	///
+	/// The key of an item is given by `key_of`. Leaves holding a single item
+	/// yield its key twice.
+	pub fn leaf_ranges<'a, K: ?Sized + 'a>(
+		&'a self,
+		key_of: impl Fn(&T) -> &K + 'a,
+	) -> impl Iterator<Item = (&'a K, &'a K)> + 'a {
+		let mut addr = self.first_item_address();
+		std::iter::from_fn(move || unsafe {
+			let current = addr?;
+			let node = self.nodes.get(current.node);
+			let last = Address::new(current.node, (node.item_count() - 1).into());
+
+			// The item following the last item of a leaf is a separator, and
+			// the one following the separator is the first item of the next
+			// leaf.
+			addr = self
+				.nodes
+				.next_item_address(last)
+				.and_then(|sep| self.nodes.next_item_address(sep));
+
+			Some((
+				key_of(self.get_at(current).unwrap()),
+				key_of(self.get_at(last).unwrap()),
+			))
+		})
+	}
+
+	/// Returns an iterator over the children of the root node, along with
+	/// their surrounding separators.
+	///
+	/// The iterator is empty if the root node is a leaf.
+	pub fn root_children(&self) -> impl Iterator<Item = (Option<&T>, S::Node, Option<&T>)> {
+		self.root
+			.into_iter()
+			.flat_map(|id| unsafe { self.nodes.get(id) }.children_with_separators())
+	}
+
+	/// Returns an iterator over the items stored in internal nodes, in order.
+	///
+	/// Those are the separators guiding the descent in the tree. The iterator
+	/// is empty if the tree has at most one node.
+	pub fn separators(&self) -> impl Iterator<Item = &T> {
+		// Stack of internal nodes, with the offset of their next separator.
+		let mut stack: Vec<(S::Node, usize)> = Vec::new();
+		let push_left = |stack: &mut Vec<_>, mut id: S::Node| loop {
+			let node = unsafe { self.nodes.get(id) };
+			if node.is_leaf() {
+				break;
+			}
+
+			stack.push((id, 0));
+			id = node.child_id(0)
+		};
+
+		if let Some(id) = self.root {
+			push_left(&mut stack, id)
+		}
+
+		std::iter::from_fn(move || loop {
+			let (id, offset) = stack.last_mut()?;
+			let node = unsafe { self.nodes.get(*id) };
+			if *offset < node.item_count() {
+				let item = node.item((*offset).into()).unwrap();
+				*offset += 1;
+				let child_id = node.child_id(*offset);
+				push_left(&mut stack, child_id);
+				break Some(item);
+			} else {
+				stack.pop();
+			}
+		})
+	}
+
+	pub fn iter_mut(&mut self) -> IterMut<'_, T, S> {
+		IterMut::new(self)
+	}
+
+	/// Returns an iterator over mutable references to the items of the tree,
+	/// in no particular order.
+	///
+	/// If the storage supports it (see [`Storage::live_nodes_mut`]), items
+	/// are visited node by node in storage order, which is more
+	/// cache-friendly than [`Self::iter_mut`] when the order doesn't matter.
+	/// Otherwise, items are visited in order.
+	pub fn iter_mut_unordered(&mut self) -> impl Iterator<Item = &mut T> {
+		// Support is checked beforehand so that the storage is only borrowed
+		// by the returned iterator.
+		let (unordered, ordered) = if self.nodes.live_nodes_mut().is_some() {
+			let nodes = self.nodes.live_nodes_mut().unwrap();
+			(Some(nodes.flat_map(Node::items_mut)), None)
+		} else {
+			(None, Some(self.iter_mut()))
+		};
+
+		unordered
+			.into_iter()
+			.flatten()
+			.chain(ordered.into_iter().flatten())
+	}
+
+	#[inline]
+	pub fn insert(&mut self, cmp: impl Fn(&T, &T) -> Ordering, item: T) -> Option<T> {
+		match self.address_of(cmp, &item) {
+			Ok(addr) => Some(unsafe { self.nodes.replace_at(addr, item) }),
+			Err(addr) => {
+				let (root, _) = unsafe { self.insert_exactly_at(addr, item) };
+				self.root = root;
+				self.len += 1;
+				None
+			}
+		}
+	}
+
+	/// Inserts an item, replacing any item comparing equal, and returns a
+	/// mutable reference to the item now in the tree.
+	pub fn insert_and_get(&mut self, cmp: impl Fn(&T, &T) -> Ordering, item: T) -> &mut T {
+		let addr = match self.address_of(cmp, &item) {
+			Ok(addr) => {
+				unsafe { self.nodes.replace_at(addr, item) };
+				addr
+			}
+			Err(addr) => {
+				let (root, new_addr) = unsafe { self.insert_exactly_at(addr, item) };
+				self.root = root;
+				self.len += 1;
+				new_addr.unwrap()
+			}
+		};
+
+		unsafe { self.get_mut_at(addr).unwrap() }
+	}
+
+	/// Insert an item expected to be greater than every other item of the
+	/// tree.
+	///
+	/// The item is directly inserted after the last item, without searching
+	/// the tree. If the item is not actually greater than the last item, this
+	/// falls back to [`Self::insert`].
+	#[inline]
+	pub fn push_back(&mut self, cmp: impl Fn(&T, &T) -> Ordering, item: T) -> Option<T> {
+		match self.last_item_address() {
+			Some(mut addr) if cmp(&item, unsafe { self.get_at(addr).unwrap() }).is_gt() => {
+				addr.offset.incr();
+				let (root, _) = unsafe { self.insert_exactly_at(Some(addr), item) };
+				self.root = root;
+				self.len += 1;
+				None
+			}
+			_ => self.insert(cmp, item),
+		}
+	}
+
+	/// Inserts the given sorted items that are not already in the tree, and
+	/// returns the number of inserted items.
+	///
+	/// Items comparing equal to an existing item, including an item inserted
+	/// earlier from the input, are ignored, or replace it if `replace` is
+	/// `true`. The tree and the input are walked together, so
+	/// the tree is not searched for each item.
+	///
+	/// In debug mode, panics if the input is not sorted.
+	pub fn insert_sorted_dedup(
+		&mut self,
+		cmp: impl Fn(&T, &T) -> Ordering,
+		sorted: impl IntoIterator<Item = T>,
+		replace: bool,
+	) -> usize {
+		let mut count = 0;
+
+		// Address of the first item not below the previous input item.
+		let mut next = self.first_item_address();
+
+		'items: for item in sorted {
+			unsafe {
+				while let Some(addr) = next {
+					match cmp(self.get_at(addr).unwrap(), &item) {
+						Ordering::Less => next = self.nodes.next_item_address(addr),
+						Ordering::Equal => {
+							if replace {
+								self.nodes.replace_at(addr, item);
+							}
+
+							continue 'items;
+						}
+						Ordering::Greater => break,
+					}
+				}
+
+				debug_assert!(
+					match next {
+						Some(addr) => self.nodes.previous_item_address(addr),
+						None => self.last_item_address(),
+					}
+					.is_none_or(|previous| cmp(self.get_at(previous).unwrap(), &item).is_lt()),
+					"input items are not sorted"
+				);
+
+				let addr = next.or_else(|| {
+					self.last_item_address().map(|mut addr| {
+						addr.offset.incr();
+						addr
+					})
+				});
+
+				let addr = addr.map(|addr| self.nodes.leaf_address(addr));
+				let (root, addr) = self.insert_exactly_at(addr, item);
+				self.root = root;
+				self.len += 1;
+				count += 1;
+				next = addr;
+			}
+		}
+
+		count
+	}
+
+	/// Inserts the given items and returns the address of each of them, in
+	/// the order of the input.
+	///
+	/// Items comparing equal to an existing item replace it, like with
+	/// [`Self::insert`]. If multiple given items compare equal, the last one
+	/// is kept and they all share the same address. The addresses are only
+	/// valid until the next mutation of the tree.
+	pub fn insert_many_with_addresses(
+		&mut self,
+		cmp: impl Fn(&T, &T) -> Ordering,
+		items: impl IntoIterator<Item = T>,
+	) -> Vec<Address<S::Node>> {
+		let mut items: Vec<(usize, T)> = items.into_iter().enumerate().collect();
+		items.sort_by(|(_, a), (_, b)| cmp(a, b));
+
+		// Insertion of sorted items doesn't change the index of the smaller
+		// ones, so the final index of each item can be computed upfront by
+		// merging them with the current items.
+		let mut indexes = vec![0; items.len()];
+		let mut current_items = self.iter().peekable();
+		let mut lower_count = 0;
+		let mut inserted_count = 0;
+		let mut i = 0;
+		while i < items.len() {
+			let item = &items[i].1;
+			while current_items.next_if(|c| cmp(c, item).is_lt()).is_some() {
+				lower_count += 1
+			}
+
+			let index = lower_count + inserted_count;
+			let mut j = i;
+			while j < items.len() && cmp(&items[j].1, item).is_eq() {
+				indexes[items[j].0] = index;
+				j += 1
+			}
+
+			if current_items.peek().is_none_or(|c| cmp(c, item).is_ne()) {
+				inserted_count += 1
+			}
+
+			i = j
+		}
+
+		for (_, item) in items {
+			self.insert(&cmp, item);
+		}
+
+		// Resolve the addresses in a single traversal.
+		let mut order: Vec<usize> = (0..indexes.len()).collect();
+		order.sort_by_key(|i| indexes[*i]);
+
+		let mut addresses = vec![None; indexes.len()];
+		let mut addr = self.first_item_address();
+		let mut index = 0;
+		for i in order {
+			while index < indexes[i] {
+				addr = unsafe { self.nodes.next_item_address(addr.unwrap()) };
+				index += 1
+			}
+
+			addresses[i] = addr
+		}
+
+		addresses.into_iter().map(Option::unwrap).collect()
+	}
+
+	/// Rebalance the tree starting from the given node.
+	///
+	/// This is the hook used to restore the tree invariants after manually
+	/// modifying a node (for instance using [`Node::split`] or
+	/// [`Node::merge`]). Overflowing nodes are split and underflowing nodes
+	/// are rotated or merged up to the root. Returns the updated `addr`.
+	///
+	/// # Safety
+	///
+	/// - The node and address's node must not have been deallocated.
+	/// - Only the given node may be out of balance, the rest of the tree must
+	///   be valid.
+	pub unsafe fn rebalance_from(
+		&mut self,
+		node: S::Node,
+		addr: Address<S::Node>,
+	) -> Option<Address<S::Node>> {
+		let (root, addr) = balancing::rebalance_observed(
+			&mut self.nodes,
+			self.root,
+			node,
+			addr,
+			self.observer.get(),
+		);
+		self.root = root;
+		addr
+	}
+
+	/// Merge the sparse leaves around the given key.
+	///
+	/// Removals only guarantee that nodes don't underflow, which may leave
+	/// many sparsely filled leaves after removing a large range of items.
+	/// This function merges the leaf where the key would be located with its
+	/// siblings, for as long as the merged leaf doesn't overflow, improving
+	/// the locality of the items around the key.
+	pub fn compact_around<Q: ?Sized>(&mut self, cmp: impl Fn(&T, &Q) -> Ordering, key: &Q) {
+		let mut id = match self.address_of(cmp, key) {
+			Ok(addr) => unsafe { self.nodes.leaf_address(addr).node },
+			Err(Some(addr)) => addr.node,
+			Err(None) => return,
+		};
+
+		while let Some(parent_id) = unsafe { self.nodes.get(id).parent() } {
+			unsafe {
+				let parent = self.nodes.get(parent_id);
+				let index = parent.child_index(id).unwrap();
+				let item_count = self.nodes.get(id).item_count();
+
+				// Checks that merging with the given sibling (and separator)
+				// would not overflow the leaf.
+				let fits = |sibling_id| item_count + self.nodes.get(sibling_id).item_count() < M;
+
+				let addr = Address::new(id, 0.into());
+				if index + 1 < parent.child_count() && fits(parent.child_id(index + 1)) {
+					self.merge_children(parent_id, index + 1, addr)
+				} else if index > 0 && fits(parent.child_id(index - 1)) {
+					id = parent.child_id(index - 1);
+					self.merge_children(parent_id, index, addr)
+				} else {
+					break;
+				}
+			}
+		}
+	}
+
+	/// Inserts an item at the given leaf address, reporting rebalancing events
+	/// to the observer.
+	///
+	/// # Safety
+	///
+	/// See [`Storage::insert_exactly_at`].
+	pub(crate) unsafe fn insert_exactly_at(
+		&mut self,
+		addr: Option<Address<S::Node>>,
+		item: T,
+	) -> (Option<S::Node>, Option<Address<S::Node>>) {
+		balancing::insert_exactly_at_observed(
+			&mut self.nodes,
+			self.root,
+			addr,
+			item,
+			None,
+			self.observer.get(),
+		)
+	}
+
+	/// Removes the item at the given address, reporting rebalancing events to
+	/// the observer.
+	///
+	/// # Safety
+	///
+	/// See [`Storage::remove_at`].
+	pub(crate) unsafe fn remove_at(
+		&mut self,
+		addr: Address<S::Node>,
+	) -> Option<storage::RemovedItem<T, S>> {
+		balancing::remove_at_observed(&mut self.nodes, self.root, addr, self.observer.get())
+	}
+
+	/// Removes the item at the given address and returns it.
+	///
+	/// # Safety
+	///
+	/// The address must be an occupied item address of the tree.
+	#[inline]
+	pub unsafe fn take_at(&mut self, addr: Address<S::Node>) -> T {
+		self.take_at_full(addr).0
+	}
+
+	/// Removes the item at the given address and returns it, along with the
+	/// address of the item that was following it, if any.
+	///
+	/// The returned address can be used to continue removing items without
+	/// searching the tree again.
+	///
+	/// # Safety
+	///
+	/// The address must be an occupied item address of the tree.
+	pub unsafe fn take_at_full(&mut self, addr: Address<S::Node>) -> (T, Option<Address<S::Node>>) {
+		let r = self.remove_at(addr).unwrap();
+		self.root = r.new_root;
+		self.len -= 1;
+		let next = r.new_addr.and_then(|addr| self.nodes.normalize(addr));
+		(r.item, next)
+	}
+
+	/// Merges the child `deficient_child_index` of the given node with one of
+	/// its siblings, and rebalances the tree.
+	///
+	/// # Safety
+	///
+	/// The node must not have been deallocated.
+	unsafe fn merge_children(
+		&mut self,
+		id: S::Node,
+		deficient_child_index: usize,
+		addr: Address<S::Node>,
+	) {
+		let mut observer = self.observer.get();
+		if let Some(observer) = &mut observer {
+			observer(balancing::merge_event(
+				&self.nodes,
+				id,
+				deficient_child_index,
+			))
+		}
+
+		balancing::merge(&mut self.nodes, id, deficient_child_index, addr);
+		let (root, _) =
+			balancing::rebalance_observed(&mut self.nodes, self.root, id, addr, observer);
+		self.root = root
+	}
+
+	/// Incrementally merges sparse leaves, performing at most `budget` merges.
+	///
+	/// Leaves are visited in order starting from the leaf `from`, or the
+	/// first leaf if `from` is `None`. Each leaf is merged with its right
+	/// sibling (under the same parent) for as long as the merged leaf doesn't
+	/// overflow, as in [`Self::compact_around`].
+	///
+	/// Returns the leaf from which the compaction can be resumed by calling
+	/// this function again, or `None` if no more leaves can be merged.
+	///
+	/// # Safety
+	///
+	/// `from` must be `None` or a leaf returned by a previous call to this
+	/// function on this tree, and the tree must not have been modified in
+	/// between. After any other modification, the compaction must be
+	/// restarted with `None`.
+	pub unsafe fn compact_step(&mut self, from: Option<S::Node>, budget: usize) -> Option<S::Node> {
+		let mut id = match from {
+			Some(id) => id,
+			None => self.first_item_address()?.node,
+		};
+
+		let mut budget = budget;
+		loop {
+			let node = self.nodes.get(id);
+			let item_count = node.item_count();
+			if let Some(parent_id) = node.parent() {
+				let parent = self.nodes.get(parent_id);
+				let index = parent.child_index(id).unwrap();
+				if index + 1 < parent.child_count()
+					&& item_count + self.nodes.get(parent.child_id(index + 1)).item_count() < M
+				{
+					if budget == 0 {
+						return Some(id);
+					}
+
+					self.merge_children(parent_id, index + 1, Address::new(id, 0.into()));
+					budget -= 1;
+					continue;
+				}
+			}
+
+			// Move on to the first leaf after the current one.
+			let last = Address::new(id, (item_count - 1).into());
+			id = self
+				.nodes
+				.next_item_address(last)
+				.and_then(|separator| self.nodes.next_item_address(separator))?
+				.node
+		}
+	}
+
+	/// Packs sibling nodes at every level of the tree, improving the average
+	/// node occupancy.
+	///
+	/// Levels are processed from the leaves up. Each node is merged with its
+	/// right sibling for as long as the merged node doesn't overflow, then
+	/// filled up to its capacity with items rotated from this sibling. The
+	/// sibling is left with fewer items, so it may in turn be merged with its
+	/// own right sibling. Unlike rebuilding the tree, nodes are merged in
+	/// place and the rest of the structure is kept.
+	pub fn rebalance_occupancy(&mut self) {
+		let mut depth = match self.height().checked_sub(1) {
+			Some(depth) => depth,
+			None => return,
+		};
+
+		while depth > 0 {
+			let height = self.height();
+			unsafe { self.compact_level(depth) };
+
+			// Merging may have removed the root, moving every level up.
+			depth = match (depth - (height - self.height())).checked_sub(1) {
+				Some(depth) => depth,
+				None => break,
+			}
+		}
+	}
+
+	/// Merges every node at the given depth with its right sibling for as
+	/// long as the merged node doesn't overflow, then fills it up to its
+	/// capacity by rotating items from this sibling.
+	///
+	/// # Safety
+	///
+	/// The tree must have a node at the given depth.
+	unsafe fn compact_level(&mut self, depth: usize) {
+		let mut id = self.root.unwrap();
+		for _ in 0..depth {
+			id = self.nodes.get(id).child_id(0)
+		}
+
+		loop {
+			let node = self.nodes.get(id);
+			let capacity = if node.is_leaf() { M } else { M - 1 };
+			let parent_id = match node.parent() {
+				Some(parent_id) => parent_id,
+				None => break,
+			};
+
+			let parent = self.nodes.get(parent_id);
+			let index = parent.child_index(id).unwrap();
+			if index + 1 < parent.child_count() {
+				if node.item_count() + self.nodes.get(parent.child_id(index + 1)).item_count()
+					< capacity
+				{
+					self.merge_children(parent_id, index + 1, Address::new(id, 0.into()));
+					continue;
+				}
+
+				while self.nodes.get(id).item_count() < capacity
+					&& self.rotate_left_into(parent_id, index)
+				{}
+			}
+
+			match self.next_node_at_same_level(id) {
+				Some(next) => id = next,
+				None => break,
+			}
+		}
+	}
+
+	/// Moves the first item of the right sibling of the child
+	/// `deficient_child_index` into this child, through the given node.
+	///
+	/// Returns `false` if the sibling would underflow.
+	///
+	/// # Safety
+	///
+	/// The node must not have been deallocated, and the child must have a
+	/// right sibling.
+	unsafe fn rotate_left_into(&mut self, id: S::Node, deficient_child_index: usize) -> bool {
+		let deficient = self.nodes.get(id).child_id(deficient_child_index);
+		let mut addr = Address::new(deficient, 0.into());
+		let rotated =
+			balancing::try_rotate_left(&mut self.nodes, id, deficient_child_index, &mut addr);
+		if rotated {
+			if let Some(observer) = self.observer.get() {
+				observer(RebalanceEvent::RotateLeft {
+					parent: id,
+					deficient,
+				})
+			}
+		}
+
+		rotated
+	}
+
+	/// Returns the node following the given one at the same depth.
+	///
+	/// # Safety
+	///
+	/// The node must not have been deallocated.
+	unsafe fn next_node_at_same_level(&self, id: S::Node) -> Option<S::Node> {
+		let parent_id = self.nodes.get(id).parent()?;
+		let parent = self.nodes.get(parent_id);
+		let index = parent.child_index(id).unwrap();
+		if index + 1 < parent.child_count() {
+			Some(parent.child_id(index + 1))
+		} else {
+			self.next_node_at_same_level(parent_id)
+				.map(|next| self.nodes.get(next).child_id(0))
+		}
+	}
+
+	/// Remove the next item and return it.
+	#[inline]
+	pub fn remove<Q: ?Sized>(&mut self, cmp: impl Fn(&T, &Q) -> Ordering, key: &Q) -> Option<T> {
+		match self.address_of(cmp, key) {
+			Ok(addr) => Some(unsafe { self.take_at(addr) }),
+			Err(_) => None,
+		}
+	}
+
+	/// Remove the item matching the given key and return it along with a
+	/// copy of its neighbors.
+	///
+	/// The neighbors are the items that were directly before and after the
+	/// removed item, prior to the removal.
+	pub fn remove_with_neighbors<Q: ?Sized>(
+		&mut self,
+		cmp: impl Fn(&T, &Q) -> Ordering,
+		key: &Q,
+	) -> Option<Removed<T>>
+	where
+		T: Clone,
+	{
+		match self.address_of(cmp, key) {
+			Ok(addr) => unsafe {
+				let previous = self
+					.nodes
+					.previous_item_address(addr)
+					.and_then(|addr| self.get_at(addr))
+					.cloned();
+				let next = self
+					.nodes
+					.next_item_address(addr)
+					.and_then(|addr| self.get_at(addr))
+					.cloned();
+
+				let r = self.remove_at(addr).unwrap();
+				self.root = r.new_root;
+				self.len -= 1;
+				Some(Removed {
+					item: r.item,
+					previous,
+					next,
+				})
+			},
+			Err(_) => None,
+		}
+	}
+
+	/// Checks that the given predicate holds for every pair of consecutive
+	/// items, in order.
+	///
+	/// Returns `true` if the tree has less than two items.
+	pub fn windows2(&self, mut f: impl FnMut(&T, &T) -> bool) -> bool {
+		let mut items = self.iter();
+		match items.next() {
+			Some(mut previous) => items.all(|item| {
+				let result = f(previous, item);
+				previous = item;
+				result
+			}),
+			None => true,
+		}
+	}
+
+	/// Calls `f` on each window of `k` consecutive items, in order.
+	///
+	/// Windows overlap, as with [`slice::windows`]. Nothing is called if the
+	/// tree has less than `k` items.
+	///
+	/// # Panics
+	///
+	/// Panics if `k` is 0.
+	pub fn windows_with(&self, k: usize, mut f: impl FnMut(&[&T])) {
+		assert!(k != 0, "window size must be non-zero");
+		let mut window = std::collections::VecDeque::with_capacity(k);
+		for item in self {
+			if window.len() == k {
+				window.pop_front();
+			}
+
+			window.push_back(item);
+			if window.len() == k {
+				f(window.make_contiguous())
+			}
+		}
+	}
+
+	/// Calls `f` on the key of each item, in order.
+	///
+	/// The key of an item is given by `key_of`.
+	pub fn for_each_key<K: ?Sized>(&self, key_of: impl Fn(&T) -> &K, mut f: impl FnMut(&K)) {
+		for item in self {
+			f(key_of(item))
+		}
+	}
+
+	/// Counts the items satisfying the given predicate.
+	///
+	/// Items are visited node by node, which is not the tree order.
+	pub fn count_matching(&self, pred: impl Fn(&T) -> bool) -> usize {
+		let mut count = 0;
+		self.visit_from_leaves(|id| {
+			let node = unsafe { self.nodes.get(id) };
+			for i in 0..node.item_count() {
+				if pred(node.item(i.into()).unwrap()) {
+					count += 1
+				}
+			}
+		});
+
+		count
+	}
+
+	/// Finds the longest run of consecutive items satisfying the given
+	/// predicate.
+	///
+	/// Returns the index of the first item of the run, and its length, or
+	/// `None` if no item satisfies the predicate. If many runs have the same
+	/// length, the first one is returned.
+	pub fn longest_run(&self, pred: impl Fn(&T) -> bool) -> Option<(usize, usize)> {
+		let mut best: Option<(usize, usize)> = None;
+		let mut start = 0;
+		let mut len = 0;
+
+		for (i, item) in self.iter().enumerate() {
+			if pred(item) {
+				if len == 0 {
+					start = i
+				}
+
+				len += 1;
+				if best.is_none_or(|(_, best_len)| len > best_len) {
+					best = Some((start, len))
+				}
+			} else {
+				len = 0
+			}
+		}
 
-	// fn first_back_address(&self) -> Address {
-	// 	match self.root {
-	// 		Some(mut id) => loop {
-	// 			match self.node(id).child_id_opt(0) {
-	// 				Some(child_id) => id = child_id,
-	// 				None => return Address::new(id, 0.into()), // TODO FIXME thechnically not the first
-	// 			}
-	// 		},
-	// 		None => Address::nowhere(),
-	// 	}
-	// }
+		best
+	}
 
-	fn last_item_address(&self) -> Option<Address<S::Node>> {
-		self.root.map(|mut id| unsafe {
-			loop {
-				let node = self.nodes.get(id);
-				let index = node.item_count();
-				match node.child_id_opt(index) {
-					Some(child_id) => id = child_id,
-					None => break Address::new(id, (index - 1).into()),
+	/// Returns an iterator over the items, coalescing consecutive items
+	/// using `f`.
+	///
+	/// `f(acc, item)` tries to merge the accumulated value `acc` with the
+	/// next item. If it returns `None`, `acc` is returned and the next item
+	/// becomes the new accumulated value. Items that are not merged with any
+	/// other are returned borrowed.
+	pub fn coalesce<'a>(
+		&'a self,
+		mut f: impl FnMut(&T, &T) -> Option<T> + 'a,
+	) -> impl Iterator<Item = Cow<'a, T>>
+	where
+		T: Clone,
+	{
+		let mut items = self.iter();
+		let mut acc = items.next().map(Cow::Borrowed);
+		std::iter::from_fn(move || {
+			let mut current = acc.take()?;
+			for item in items.by_ref() {
+				match f(&current, item) {
+					Some(merged) => current = Cow::Owned(merged),
+					None => {
+						acc = Some(Cow::Borrowed(item));
+						break;
+					}
 				}
 			}
+
+			Some(current)
 		})
 	}
 
-	// fn last_valid_address(&self) -> Address {
-	// 	match self.root {
-	// 		Some(mut id) => loop {
-	// 			let node = self.node(id);
-	// 			let index = node.item_count();
-	// 			match node.child_id_opt(index) {
-	// 				Some(child_id) => id = child_id,
-	// 				None => return Address::new(id, index.into()),
-	// 			}
-	// 		},
-	// 		None => Address::nowhere(),
-	// 	}
-	// }
-
-	/// Return the item at the given address.
+	/// Returns an iterator over the gaps between consecutive items.
 	///
-	/// # Safety
+	/// Two consecutive items `a` and `b` delimit a gap when `adjacent(a, b)`
+	/// is `false`. The gap is returned as the open interval `(a, b)`, whose
+	/// first missing item is `succ(a)`. If `succ(a)` is `None`, the interval
+	/// is empty and no gap is returned.
 	///
-	/// The address's node must not have been deallocated.
-	#[inline]
-	pub unsafe fn get_at(&self, addr: Address<S::Node>) -> Option<&T> {
-		self.nodes.get(addr.node).item(addr.offset)
+	/// For instance, for the items `1, 2, 5, 6` the gap `(2, 5)` is returned.
+	pub fn gaps<'a>(
+		&'a self,
+		succ: impl Fn(&T) -> Option<T> + 'a,
+		adjacent: impl Fn(&T, &T) -> bool + 'a,
+	) -> impl Iterator<Item = (T, T)> + 'a
+	where
+		T: Clone,
+	{
+		let mut items = self.iter();
+		let mut previous = items.next();
+		items.filter_map(move |item| {
+			let a = previous.replace(item).unwrap();
+			if adjacent(a, item) || succ(a).is_none() {
+				None
+			} else {
+				Some((a.clone(), item.clone()))
+			}
+		})
 	}
 
-	/// Returns a mutable reference to the item at the given address.
+	/// Returns an iterator over the items, in order, for as long as the sum
+	/// of their weights does not exceed `limit`.
 	///
-	/// # Safety
-	///
-	/// The address's node must not have been deallocated.
-	#[inline]
-	pub unsafe fn get_mut_at(&mut self, addr: Address<S::Node>) -> Option<&mut T> {
-		self.nodes.get_mut(addr.node).item_mut(addr.offset)
+	/// The iteration stops at the first item whose weight would make the sum
+	/// exceed the limit, and the iterator is fused: lighter items following
+	/// this one are never returned.
+	pub fn take_while_sum<'a>(
+		&'a self,
+		weight: impl Fn(&T) -> u64 + 'a,
+		limit: u64,
+	) -> impl Iterator<Item = &'a T> {
+		self.iter()
+			.scan(0u64, move |sum, item| {
+				*sum = sum.checked_add(weight(item)).filter(|s| *s <= limit)?;
+				Some(item)
+			})
+			.fuse()
 	}
 
-	#[inline]
-	pub fn get<Q: ?Sized>(&self, cmp: impl Fn(&T, &Q) -> Ordering, key: &Q) -> Option<&T> {
-		self.address_of(cmp, key)
-			.ok()
-			.and_then(|addr| unsafe { self.get_at(addr) })
+	/// Returns an iterator over the local maxima of the tree, according to the
+	/// value `value` associated to each item.
+	///
+	/// An item is a local maximum if its value is strictly greater than the
+	/// value of both its in-order neighbors. The first and last items are only
+	/// compared to their single neighbor.
+	pub fn local_maxima<'a, V>(
+		&'a self,
+		value: impl Fn(&T) -> V + 'a,
+		v_cmp: impl Fn(&V, &V) -> Ordering + 'a,
+	) -> impl Iterator<Item = &'a T> {
+		let mut items = self.iter().peekable();
+		let mut previous: Option<V> = None;
+		std::iter::from_fn(move || loop {
+			let item = items.next()?;
+			let v = value(item);
+			let is_maximum = previous.as_ref().is_none_or(|p| v_cmp(&v, p).is_gt())
+				&& items
+					.peek()
+					.is_none_or(|next| v_cmp(&v, &value(next)).is_gt());
+			previous = Some(v);
+
+			if is_maximum {
+				break Some(item);
+			}
+		})
 	}
 
-	#[inline]
-	pub fn get_mut<Q: ?Sized>(
-		&mut self,
+	/// Checks if no item lies in the given key range.
+	///
+	/// Only the first item not below the start bound is compared to the end
+	/// bound.
+	pub fn range_is_empty<Q: ?Sized>(
+		&self,
 		cmp: impl Fn(&T, &Q) -> Ordering,
-		key: &Q,
-	) -> Option<&mut T> {
-		self.address_of(cmp, key)
-			.ok()
-			.and_then(|addr| unsafe { self.get_mut_at(addr) })
+		range: impl RangeBounds<Q>,
+	) -> bool {
+		match self.lower_bound_address(&cmp, range.start_bound()) {
+			Some(addr) => {
+				let item = unsafe { self.get_at(addr).unwrap() };
+				!utils::is_below_upper_bound(&cmp, item, range.end_bound())
+			}
+			None => true,
+		}
 	}
 
-	#[inline]
-	pub fn first(&self) -> Option<&T> {
-		self.first_item_address()
-			.and_then(|addr| unsafe { self.get_at(addr) })
+	/// Returns the item of the given key range with the greatest value
+	/// `value`.
+	///
+	/// If several items share the greatest value, the first one is returned.
+	/// Returns `None` if the range is empty.
+	pub fn max_value_in_range<Q: ?Sized, V>(
+		&self,
+		cmp: impl Fn(&T, &Q) -> Ordering,
+		range: impl RangeBounds<Q>,
+		value: impl Fn(&T) -> V,
+		v_cmp: impl Fn(&V, &V) -> Ordering,
+	) -> Option<&T> {
+		let mut max: Option<(&T, V)> = None;
+		for item in self.items_in_range(cmp, range) {
+			let v = value(item);
+			if max.as_ref().is_none_or(|(_, m)| v_cmp(&v, m).is_gt()) {
+				max = Some((item, v))
+			}
+		}
+
+		max.map(|(item, _)| item)
 	}
 
-	#[inline]
-	pub fn first_mut(&mut self) -> Option<&mut T> {
-		self.first_item_address()
-			.and_then(|addr| unsafe { self.get_mut_at(addr) })
+	/// Returns the first item of the given key range satisfying `pred`.
+	pub fn find_in_range<Q: ?Sized>(
+		&self,
+		cmp: impl Fn(&T, &Q) -> Ordering,
+		range: impl RangeBounds<Q>,
+		pred: impl Fn(&T) -> bool,
+	) -> Option<&T> {
+		self.items_in_range(cmp, range).find(|item| pred(item))
 	}
 
-	#[inline]
-	pub fn last(&self) -> Option<&T> {
-		self.last_item_address()
-			.and_then(|addr| unsafe { self.get_at(addr) })
+	/// Returns an iterator over the items of the given key range, in order.
+	fn items_in_range<Q: ?Sized>(
+		&self,
+		cmp: impl Fn(&T, &Q) -> Ordering,
+		range: impl RangeBounds<Q>,
+	) -> impl Iterator<Item = &T> {
+		let mut addr = self.lower_bound_address(&cmp, range.start_bound());
+		std::iter::from_fn(move || {
+			let a = addr?;
+			let item = unsafe { self.get_at(a).unwrap() };
+			if utils::is_below_upper_bound(&cmp, item, range.end_bound()) {
+				addr = unsafe { self.nodes.next_item_address(a) };
+				Some(item)
+			} else {
+				addr = None;
+				None
+			}
+		})
 	}
 
-	#[inline]
-	pub fn last_mut(&mut self) -> Option<&mut T> {
-		self.last_item_address()
-			.and_then(|addr| unsafe { self.get_mut_at(addr) })
+	/// Walks this tree and the `other` tree in order and returns the first
+	/// difference found between the two.
+	///
+	/// Items are matched using `cmp`, and matching items are compared using
+	/// `eq`. Returns `None` if both trees contain the same items.
+	pub fn first_difference<'a>(
+		&'a self,
+		cmp: impl Fn(&T, &T) -> Ordering,
+		eq: impl Fn(&T, &T) -> bool,
+		other: &'a Self,
+	) -> Option<Diff<&'a T>> {
+		let mut left = self.iter();
+		let mut right = other.iter();
+
+		loop {
+			match (left.next(), right.next()) {
+				(None, None) => break None,
+				(Some(a), None) => break Some(Diff::Left(a)),
+				(None, Some(b)) => break Some(Diff::Right(b)),
+				(Some(a), Some(b)) => match cmp(a, b) {
+					Ordering::Less => break Some(Diff::Left(a)),
+					Ordering::Greater => break Some(Diff::Right(b)),
+					Ordering::Equal => {
+						if !eq(a, b) {
+							break Some(Diff::Changed(a, b));
+						}
+					}
+				},
+			}
+		}
 	}
 
-	pub fn iter(&self) -> Iter<T, S> {
-		Iter::new(self)
+	/// Walks this tree and the `other` tree in order and returns every
+	/// difference between the two.
+	///
+	/// Items are matched using `cmp`, and matching items are compared using
+	/// `eq`. Items only found in this tree are returned as [`Diff::Left`],
+	/// items only found in `other` as [`Diff::Right`].
+	pub fn diff<'a>(
+		&'a self,
+		cmp: impl Fn(&T, &T) -> Ordering + 'a,
+		eq: impl Fn(&T, &T) -> bool + 'a,
+		other: &'a Self,
+	) -> impl Iterator<Item = Diff<&'a T>> {
+		let mut left = self.iter().peekable();
+		let mut right = other.iter().peekable();
+
+		std::iter::from_fn(move || loop {
+			match (left.peek(), right.peek()) {
+				(None, None) => break None,
+				(Some(_), None) => break left.next().map(Diff::Left),
+				(None, Some(_)) => break right.next().map(Diff::Right),
+				(Some(a), Some(b)) => match cmp(a, b) {
+					Ordering::Less => break left.next().map(Diff::Left),
+					Ordering::Greater => break right.next().map(Diff::Right),
+					Ordering::Equal => {
+						let a = left.next().unwrap();
+						let b = right.next().unwrap();
+						if !eq(a, b) {
+							break Some(Diff::Changed(a, b));
+						}
+					}
+				},
+			}
+		})
 	}
 
-	pub fn iter_mut(&mut self) -> IterMut<T, S> {
-		IterMut::new(self)
+	/// Removes the items in the given range, returning them as an iterator.
+	///
+	/// If the iterator is dropped before being fully consumed, the remaining
+	/// items of the range are still removed.
+	pub fn drain_range<C, R, Q: ?Sized>(
+		&mut self,
+		cmp: C,
+		range: R,
+	) -> DrainRange<'_, T, C, R, Q, S>
+	where
+		C: Fn(&T, &Q) -> Ordering,
+		R: RangeBounds<Q>,
+	{
+		DrainRange::new(self, cmp, range)
 	}
 
-	#[inline]
-	pub fn insert(&mut self, cmp: impl Fn(&T, &T) -> Ordering, item: T) -> Option<T> {
-		match self.address_of(cmp, &item) {
-			Ok(addr) => Some(unsafe { self.nodes.replace_at(addr, item) }),
-			Err(addr) => {
-				let (root, _) =
-					unsafe { self.nodes.insert_exactly_at(self.root, addr, item, None) };
-				self.root = root;
-				self.len += 1;
-				None
-			}
+	/// Replaces the items in the given range with the given sorted items.
+	///
+	/// Items are compared using `cmp`, and with the range bounds using
+	/// `key_cmp`. The replacement items must be strictly sorted and lie
+	/// within the range, which is checked in debug mode.
+	pub fn splice_range<Q: ?Sized>(
+		&mut self,
+		cmp: impl Fn(&T, &T) -> Ordering,
+		key_cmp: impl Fn(&T, &Q) -> Ordering,
+		range: impl RangeBounds<Q>,
+		replacement: &[T],
+	) where
+		T: Clone,
+	{
+		let start = range.start_bound();
+		let end = range.end_bound();
+		std::mem::drop(self.drain_range(&key_cmp, (start, end)));
+
+		// Address of the item before which the next item is inserted.
+		let mut next = self.lower_bound_address(&key_cmp, start);
+		let mut previous: Option<&T> = None;
+		for item in replacement {
+			debug_assert!(
+				match start {
+					Bound::Unbounded => true,
+					Bound::Included(key) => key_cmp(item, key).is_ge(),
+					Bound::Excluded(key) => key_cmp(item, key).is_gt(),
+				} && utils::is_below_upper_bound(&key_cmp, item, end),
+				"replacement item out of range"
+			);
+			debug_assert!(
+				previous.is_none_or(|previous| cmp(previous, item).is_lt()),
+				"replacement items are not sorted"
+			);
+			previous = Some(item);
+
+			let addr = next.or_else(|| {
+				self.last_item_address().map(|mut addr| {
+					addr.offset.incr();
+					addr
+				})
+			});
+
+			let addr = addr.map(|addr| unsafe { self.nodes.leaf_address(addr) });
+			let (root, addr) = unsafe { self.insert_exactly_at(addr, item.clone()) };
+			self.root = root;
+			self.len += 1;
+			next = addr.and_then(|addr| unsafe { self.nodes.next_item_address(addr) });
 		}
 	}
 
-	/// Remove the next item and return it.
-	#[inline]
-	pub fn remove<Q: ?Sized>(&mut self, cmp: impl Fn(&T, &Q) -> Ordering, key: &Q) -> Option<T> {
-		match self.address_of(cmp, key) {
-			Ok(addr) => {
-				let r = unsafe { self.nodes.remove_at(self.root, addr).unwrap() };
-				self.root = r.new_root;
-				self.len -= 1;
-				Some(r.item)
-			}
-			Err(_) => None,
-		}
+	/// Returns an iterator over the nodes of the tree in breadth-first order,
+	/// along with their depth, the root node being at depth `0`.
+	pub fn bfs(&self) -> impl Iterator<Item = (usize, S::Node)> + '_ {
+		let mut queue: std::collections::VecDeque<_> =
+			self.root.map(|id| (0, id)).into_iter().collect();
+		std::iter::from_fn(move || {
+			let (depth, id) = queue.pop_front()?;
+			let node = unsafe { self.nodes.get(id) };
+			queue.extend(node.children().map(|child_id| (depth + 1, child_id)));
+			Some((depth, id))
+		})
 	}
 
 	pub fn visit_from_leaves(&self, mut f: impl FnMut(S::Node)) {
@@ -231,6 +2645,23 @@ impl<T, S: Storage<T>> RawBTree<T, S> {
 		}
 	}
 
+	/// Visit the nodes of the tree from the leaves, until `f` returns
+	/// [`ControlFlow::Break`].
+	///
+	/// Nodes are visited in the same order as [`Self::visit_from_leaves`].
+	pub fn try_visit_from_leaves<B>(
+		&self,
+		mut f: impl FnMut(S::Node) -> ControlFlow<B>,
+	) -> ControlFlow<B> {
+		if let Some(id) = self.root {
+			let node = unsafe { self.nodes.get(id) };
+			node.try_visit_from_leaves(&self.nodes, &mut f)?;
+			f(id)?
+		}
+
+		ControlFlow::Continue(())
+	}
+
 	pub fn visit_from_leaves_mut(&mut self, mut f: impl FnMut(S::Node, &mut Node<T, S>)) {
 		if let Some(root_id) = self.root {
 			let root_node: &mut Node<T, S> =
@@ -253,18 +2684,135 @@ impl<T, S: Storage<T>> RawBTree<T, S> {
 
 		self.root = None;
 		self.len = 0;
-		self.nodes = S::default();
+		self.nodes = self.nodes.empty();
 	}
 
+	/// Clear the tree, dropping every item.
+	///
+	/// Nodes are dropped iteratively using an explicit stack, so the call
+	/// stack usage doesn't depend on the tree height.
 	pub fn clear(&mut self) {
 		use storage::Dropper;
 		if let Some(mut dropper) = self.nodes.start_dropping() {
-			self.visit_from_leaves(|id| unsafe { dropper.drop_node(id) })
+			let mut stack: Vec<S::Node> = self.root.into_iter().collect();
+			while let Some(id) = stack.pop() {
+				unsafe {
+					stack.extend(self.nodes.get(id).children());
+					dropper.drop_node(id)
+				}
+			}
+		}
+
+		self.root = None;
+		self.len = 0;
+		self.nodes = self.nodes.empty();
+	}
+
+	/// Clear the tree while keeping the storage.
+	///
+	/// Unlike [`Self::clear`], every node is released to the storage using
+	/// [`Storage::release_node`] instead of replacing the storage, so that
+	/// the storage can reuse the nodes afterward.
+	pub fn clear_keeping_storage(&mut self) {
+		let mut ids = Vec::new();
+		self.visit_from_leaves(|id| ids.push(id));
+
+		for id in ids {
+			unsafe { self.nodes.release_node(id) };
 		}
 
 		self.root = None;
 		self.len = 0;
-		self.nodes = S::default();
+	}
+
+	/// Replace the content of the tree with the items of the given sorted
+	/// iterator.
+	///
+	/// Current nodes are released to the storage before the new items are
+	/// appended (see [`Self::clear_keeping_storage`] and [`Self::push_back`]).
+	/// Items that are not sorted are still correctly inserted, at the cost of
+	/// a regular insertion.
+	pub fn reset_from_sorted_iter(
+		&mut self,
+		cmp: impl Fn(&T, &T) -> Ordering,
+		items: impl IntoIterator<Item = T>,
+	) {
+		self.clear_keeping_storage();
+		for item in items {
+			self.push_back(&cmp, item);
+		}
+	}
+
+	/// Computes a fingerprint of both the items and the shape of the tree.
+	///
+	/// Nodes are hashed in pre-order, along with their item and child count,
+	/// so that two trees with the same items but different layouts have
+	/// different hashes. The hasher uses fixed keys, so the result is
+	/// stable across runs of the same build, but may change with the Rust
+	/// release or the target platform.
+	pub fn structural_hash(&self) -> u64
+	where
+		T: Hash,
+	{
+		let mut hasher = std::collections::hash_map::DefaultHasher::new();
+		if let Some(id) = self.root {
+			self.structural_hash_node(id, &mut hasher)
+		}
+
+		hasher.finish()
+	}
+
+	fn structural_hash_node<H: Hasher>(&self, id: S::Node, hasher: &mut H)
+	where
+		T: Hash,
+	{
+		let node = unsafe { self.nodes.get(id) };
+		hasher.write_usize(node.item_count());
+		hasher.write_usize(node.child_count());
+
+		for offset in 0..node.item_count() {
+			node.item(offset.into()).unwrap().hash(hasher)
+		}
+
+		for child_id in node.children() {
+			self.structural_hash_node(child_id, hasher)
+		}
+	}
+
+	/// Checks that the parent of every node is the node pointing to it.
+	///
+	/// Returns the first node, in pre-order, whose parent is inconsistent.
+	pub fn check_parents(&self) -> Result<(), S::Node> {
+		let mut stack: Vec<(Option<S::Node>, S::Node)> =
+			self.root.map(|id| (None, id)).into_iter().collect();
+		while let Some((parent, id)) = stack.pop() {
+			let node = unsafe { self.nodes.get(id) };
+			if node.parent() != parent {
+				return Err(id);
+			}
+
+			let children: Array<S::Node, M> = node.children().collect();
+			stack.extend(children.iter().rev().map(|child| (Some(id), *child)))
+		}
+
+		Ok(())
+	}
+
+	/// Checks that the number of items of the tree matches its length.
+	///
+	/// Iterates over the tree and counts the items stored in every node,
+	/// returning `false` if either count differs from [`Self::len`], or if
+	/// items remain after the iterator is exhausted. Unlike
+	/// [`Self::validate`], this does not check the order of items and is
+	/// available in release builds.
+	pub fn self_check_count(&self) -> bool {
+		let mut iter = self.iter();
+		let visited = iter.by_ref().count();
+
+		let mut stored = 0;
+		self.visit_from_leaves(|id| stored += unsafe { self.nodes.get(id) }.item_count());
+
+		visited == self.len && iter.addr.is_none() && stored == self.len
 	}
 
 	#[cfg(debug_assertions)]
@@ -274,6 +2822,80 @@ impl<T, S: Storage<T>> RawBTree<T, S> {
 		}
 	}
 
+	/// Checks that moving to the previous and next item addresses of every
+	/// item of the tree round-trips.
+	///
+	/// Panics if `next_item_address(previous_item_address(addr))` or
+	/// `previous_item_address(next_item_address(addr))` is not `addr`.
+	#[cfg(debug_assertions)]
+	pub fn validate_address_navigation(&self) {
+		let mut count = 0;
+		self.visit_from_leaves(|id| unsafe {
+			for addr in self.node_addresses(id) {
+				count += 1;
+
+				match self.nodes.previous_item_address(addr) {
+					Some(previous) => {
+						if self.nodes.next_item_address(previous) != Some(addr) {
+							panic!("next item address of the previous item address differs")
+						}
+					}
+					None => {
+						if Some(addr) != self.first_item_address() {
+							panic!("missing previous item address")
+						}
+					}
+				}
+
+				match self.nodes.next_item_address(addr) {
+					Some(next) => {
+						if self.nodes.previous_item_address(next) != Some(addr) {
+							panic!("previous item address of the next item address differs")
+						}
+					}
+					None => {
+						if Some(addr) != self.last_item_address() {
+							panic!("missing next item address")
+						}
+					}
+				}
+			}
+		});
+
+		if count != self.len {
+			panic!("wrong item count")
+		}
+	}
+
+	/// Validate the subtree rooted in the given node and returns the depth of
+	/// the node.
+	///
+	/// The node items are checked against the nearest separators found in
+	/// its ancestors.
+	#[cfg(debug_assertions)]
+	pub fn validate_subtree(&self, cmp: impl Fn(&T, &T) -> Ordering, id: S::Node) -> usize {
+		let parent = unsafe { self.nodes.get(id).parent() };
+
+		let mut min = None;
+		let mut max = None;
+		let mut child_id = id;
+		while min.is_none() || max.is_none() {
+			match unsafe { self.nodes.get(child_id).parent() } {
+				Some(parent_id) => {
+					let parent = unsafe { self.nodes.get(parent_id) };
+					let (child_min, child_max) =
+						parent.separators(parent.child_index(child_id).unwrap());
+					min = min.or(child_min);
+					max = max.or(child_max);
+					child_id = parent_id
+				}
+				None => break,
+			}
+		}
+
+		self.validate_node(&cmp, id, parent, min, max)
+	}
+
 	/// Validate the given node and returns the depth of the node.
 	#[cfg(debug_assertions)]
 	pub fn validate_node(
@@ -358,6 +2980,95 @@ impl<T, S: Storage<T>> RawBTree<T, S> {
 	}
 }
 
+#[cfg(feature = "rayon")]
+impl<T: Send> RawBTree<T, BoxStorage> {
+	/// Clear the tree, dropping the subtrees of the root node in parallel.
+	///
+	/// Useful to drop very large trees whose items have an expensive
+	/// destructor. Requires the `rayon` feature.
+	pub fn par_clear(&mut self) {
+		use rayon::prelude::*;
+		use storage::Dropper;
+
+		if let Some(root_id) = self.root.take() {
+			let root = unsafe { self.nodes.release_node(root_id) };
+			let children: Vec<_> = root.children().collect();
+			std::mem::drop(root);
+
+			let nodes = &self.nodes;
+			children.into_par_iter().for_each(|id| unsafe {
+				let mut dropper = storage::BoxDrop;
+				nodes
+					.get(id)
+					.visit_from_leaves(nodes, |c| dropper.drop_node(c));
+				dropper.drop_node(id)
+			});
+		}
+
+		self.len = 0;
+	}
+}
+
+impl<T: Clone> RawBTree<T, CowStorage<T>> {
+	/// Returns an iterator over the items of the tree held by nodes that are
+	/// not shared with the `base` clone, in order.
+	///
+	/// Every item inserted or modified since `base` was cloned is returned,
+	/// along with the other items of the nodes that have been copied since.
+	/// Removed items are not returned. Both trees are walked together from
+	/// their root, skipping every shared subtree, so only the copied nodes
+	/// are visited.
+	pub fn changed_since<'a>(&'a self, base: &'a Self) -> impl Iterator<Item = &'a T> {
+		let is_shared = move |id| self.nodes.shares_node(&base.nodes, id);
+
+		// Stack of copied nodes, along with their next step: even steps
+		// visit the child before the item at offset `step / 2`, odd steps
+		// return this item.
+		let mut stack: Vec<(usize, usize)> = self
+			.root
+			.filter(|id| !is_shared(*id))
+			.map(|id| (id, 0))
+			.into_iter()
+			.collect();
+
+		std::iter::from_fn(move || loop {
+			let (id, step) = stack.last_mut()?;
+			let node = unsafe { self.nodes.get(*id) };
+			let offset = *step / 2;
+			let visit_child = *step % 2 == 0;
+			*step += 1;
+
+			if visit_child {
+				if let Some(child_id) = node.child_id_opt(offset) {
+					if !is_shared(child_id) {
+						stack.push((child_id, 0))
+					}
+				}
+			} else {
+				match node.item(offset.into()) {
+					Some(item) => break Some(item),
+					None => {
+						stack.pop();
+					}
+				}
+			}
+		})
+	}
+}
+
+/// Item removed with [`RawBTree::remove_with_neighbors`].
+#[derive(Debug, Clone)]
+pub struct Removed<T> {
+	/// Removed item.
+	pub item: T,
+
+	/// Item that was directly before the removed item, if any.
+	pub previous: Option<T>,
+
+	/// Item that was directly after the removed item, if any.
+	pub next: Option<T>,
+}
+
 impl<T, S: Storage<T>> Drop for RawBTree<T, S> {
 	fn drop(&mut self) {
 		self.clear();
@@ -365,7 +3076,21 @@ impl<T, S: Storage<T>> Drop for RawBTree<T, S> {
 }
 
 impl<T: Clone, S: Storage<T>> Clone for RawBTree<T, S> {
+	/// Clones the tree.
+	///
+	/// If the storage can share its nodes (see [`Storage::share`]), such as
+	/// [`CowStorage`], this is O(1). Otherwise every node is copied.
 	fn clone(&self) -> Self {
+		if let Some(nodes) = self.nodes.share() {
+			return Self {
+				nodes,
+				root: self.root,
+				len: self.len,
+				observer: ObserverSlot::new(),
+				item: PhantomData,
+			};
+		}
+
 		unsafe fn clone_node<T: Clone, S: Storage<T>>(
 			old_nodes: &S,
 			new_nodes: &mut S,
@@ -391,7 +3116,7 @@ impl<T: Clone, S: Storage<T>> Clone for RawBTree<T, S> {
 			new_nodes.insert_node(clone)
 		}
 
-		let mut nodes = S::default();
+		let mut nodes = self.nodes.empty();
 		let root = self
 			.root
 			.map(|root| unsafe { clone_node(&self.nodes, &mut nodes, None, root) });
@@ -400,6 +3125,7 @@ impl<T: Clone, S: Storage<T>> Clone for RawBTree<T, S> {
 			nodes,
 			root,
 			len: self.len,
+			observer: ObserverSlot::new(),
 			item: PhantomData,
 		}
 	}
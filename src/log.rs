@@ -0,0 +1,11 @@
+/// Operation of a tree log.
+///
+/// Replayed with [`RawBTree::replay`](crate::RawBTree::replay).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogOp<T, Q> {
+	/// Inserts an item, replacing any item comparing equal.
+	Insert(T),
+
+	/// Removes the item matching the given key, if any.
+	Remove(Q),
+}
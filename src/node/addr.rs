@@ -102,6 +102,15 @@ impl<T> Address<T> {
 		Address { node: id, offset }
 	}
 
+	/// Returns the index of the addressed item in its node.
+	///
+	/// Returns `None` if the offset is `-1`. The returned index may be equal
+	/// to the number of items in the node for back addresses.
+	#[inline]
+	pub fn item_index(&self) -> Option<usize> {
+		self.offset.to_index()
+	}
+
 	// /// Address in the empty tree.
 	// ///
 	// /// This is the unique valid address address in an ampty tree.
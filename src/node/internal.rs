@@ -4,7 +4,7 @@ use crate::{
 };
 use std::cmp::Ordering;
 
-use super::{Balance, Children, ChildrenWithSeparators, Offset, WouldUnderflow};
+use super::{Balance, Children, ChildrenWithSeparators, Offset, SplitBias, WouldUnderflow};
 
 /// Underflow threshold.
 ///
@@ -53,7 +53,7 @@ pub struct InsertionError<T, S: Storage<T>> {
 /// Internal node.
 ///
 /// An internal node is a node where each item is surrounded by edges to child nodes.
-// #[derive(Clone)]
+#[derive(Clone)]
 pub struct Internal<T, S: Storage<T>> {
 	parent: Option<S::Node>,
 	first_child: S::Node,
@@ -153,6 +153,11 @@ impl<T, S: Storage<T>> Internal<T, S> {
 		self.other_children.as_ref()
 	}
 
+	#[inline]
+	pub(super) fn branches_iter_mut(&mut self) -> std::slice::IterMut<'_, Branch<T, S>> {
+		self.other_children.iter_mut()
+	}
+
 	#[inline]
 	pub fn child_index(&self, id: S::Node) -> Option<usize> {
 		if self.first_child == id {
@@ -261,12 +266,12 @@ impl<T, S: Storage<T>> Internal<T, S> {
 	}
 
 	#[inline]
-	pub fn children(&self) -> Children<T, S> {
+	pub fn children(&self) -> Children<'_, T, S> {
 		Children::Internal(Some(self.first_child), self.other_children.as_ref().iter())
 	}
 
 	#[inline]
-	pub fn children_with_separators(&self) -> ChildrenWithSeparators<T, S> {
+	pub fn children_with_separators(&self) -> ChildrenWithSeparators<'_, T, S> {
 		ChildrenWithSeparators::Internal(
 			Some(self.first_child),
 			None,
@@ -276,7 +281,7 @@ impl<T, S: Storage<T>> Internal<T, S> {
 
 	#[inline]
 	pub fn item(&self, offset: Offset) -> Option<&T> {
-		match self.other_children.get(offset.unwrap()) {
+		match self.other_children.get(offset.value()?) {
 			Some(b) => Some(&b.item),
 			None => None,
 		}
@@ -284,12 +289,22 @@ impl<T, S: Storage<T>> Internal<T, S> {
 
 	#[inline]
 	pub fn item_mut(&mut self, offset: Offset) -> Option<&mut T> {
-		match self.other_children.get_mut(offset.unwrap()) {
+		match self.other_children.get_mut(offset.value()?) {
 			Some(b) => Some(&mut b.item),
 			None => None,
 		}
 	}
 
+	/// Swaps the items at the given offsets, keeping the children in place.
+	#[inline]
+	pub fn swap_items(&mut self, a: Offset, b: Offset) {
+		let (i, j) = (a.unwrap().min(b.unwrap()), a.unwrap().max(b.unwrap()));
+		if i != j {
+			let (left, right) = self.other_children.split_at_mut(j);
+			std::mem::swap(&mut left[i].item, &mut right[0].item)
+		}
+	}
+
 	/// Insert by key.
 	#[inline]
 	pub fn insert_by_key(
@@ -367,10 +382,18 @@ impl<T, S: Storage<T>> Internal<T, S> {
 
 	#[inline]
 	pub fn split(&mut self) -> (usize, T, Internal<T, S>) {
+		self.split_biased(SplitBias::Middle)
+	}
+
+	#[inline]
+	pub fn split_biased(&mut self, bias: SplitBias) -> (usize, T, Internal<T, S>) {
 		assert!(self.is_overflowing()); // implies self.other_children.len() >= 4
 
 		// Index of the median-key item in `other_children`.
-		let median_i = (self.other_children.len() - 1) / 2; // Since M is at least 3, `median_i` is at least 1.
+		let median_i = match bias {
+			SplitBias::Middle => (self.other_children.len() - 1) / 2, // Since M is at least 3, `median_i` is at least 1.
+			SplitBias::Right => self.other_children.len() - 1 - UNDERFLOW,
+		};
 
 		let right_other_children = self.other_children.drain(median_i + 1..).collect();
 		let median = self.other_children.pop().unwrap();
@@ -387,6 +410,28 @@ impl<T, S: Storage<T>> Internal<T, S> {
 		(self.other_children.len(), median.item, right_node)
 	}
 
+	/// Moves the items from the given offset, and the children following
+	/// them, into a new node without parent whose first child is
+	/// `first_child`.
+	///
+	/// This node keeps its children up to the one at index `offset`.
+	#[inline]
+	pub fn split_off(&mut self, offset: usize, first_child: S::Node) -> Internal<T, S> {
+		Internal {
+			parent: None,
+			first_child,
+			other_children: self.other_children.drain(offset..).collect(),
+		}
+	}
+
+	/// Returns an iterator over mutable references to the children
+	/// identifiers.
+	#[inline]
+	pub fn children_mut(&mut self) -> impl Iterator<Item = &mut S::Node> {
+		std::iter::once(&mut self.first_child)
+			.chain(self.other_children.iter_mut().map(|b| &mut b.child))
+	}
+
 	/// Merge the children at the given indexes.
 	///
 	/// It is supposed that `left_index` is `right_index-1`.
@@ -505,26 +550,36 @@ impl<T, S: Storage<T>> Internal<T, S> {
 			panic!("root node is empty")
 		}
 
-		if !self
-			.other_children
-			.windows(2)
-			.all(|w| cmp(&w[0].item, &w[1].item).is_lt())
-		{
-			panic!("internal node items are not sorted")
+		for w in self.other_children.windows(2) {
+			match cmp(&w[0].item, &w[1].item) {
+				Ordering::Less => (),
+				Ordering::Equal => panic!("internal node contains a duplicate key"),
+				Ordering::Greater => panic!("internal node items are not sorted"),
+			}
 		}
 
 		if let Some(min) = min {
 			if let Some(b) = self.other_children.first() {
-				if cmp(min, &b.item).is_ge() {
-					panic!("internal node item key is greater than right separator")
+				match cmp(min, &b.item) {
+					Ordering::Less => (),
+					Ordering::Equal => {
+						panic!("internal node item key is a duplicate of its separator")
+					}
+					Ordering::Greater => {
+						panic!("internal node item key is greater than right separator")
+					}
 				}
 			}
 		}
 
 		if let Some(max) = max {
 			if let Some(b) = self.other_children.last() {
-				if cmp(max, &b.item).is_le() {
-					panic!("internal node item key is less than left separator")
+				match cmp(max, &b.item) {
+					Ordering::Greater => (),
+					Ordering::Equal => {
+						panic!("internal node item key is a duplicate of its separator")
+					}
+					Ordering::Less => panic!("internal node item key is less than left separator"),
 				}
 			}
 		}
@@ -5,7 +5,7 @@ use crate::{
 	Storage, M,
 };
 
-use super::{Balance, Offset, WouldUnderflow};
+use super::{Balance, Offset, SplitBias, WouldUnderflow};
 
 #[derive(Clone)]
 pub struct Leaf<T, S: Storage<T>> {
@@ -52,10 +52,15 @@ impl<T, S: Storage<T>> Leaf<T, S> {
 	}
 
 	#[inline]
-	pub fn iter(&self) -> std::slice::Iter<T> {
+	pub fn iter(&self) -> std::slice::Iter<'_, T> {
 		self.items.as_ref().iter()
 	}
 
+	#[inline]
+	pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+		self.items.iter_mut()
+	}
+
 	#[inline]
 	pub fn get<Q: ?Sized>(&self, cmp: impl Fn(&T, &Q) -> Ordering, key: &Q) -> Option<&T> {
 		match binary_search_min(cmp, &self.items, key) {
@@ -123,6 +128,11 @@ impl<T, S: Storage<T>> Leaf<T, S> {
 		}
 	}
 
+	#[inline]
+	pub fn swap_items(&mut self, a: Offset, b: Offset) {
+		self.items.swap(a.unwrap(), b.unwrap())
+	}
+
 	#[inline]
 	pub fn insert_by_key(
 		&mut self,
@@ -148,9 +158,19 @@ impl<T, S: Storage<T>> Leaf<T, S> {
 
 	#[inline]
 	pub fn split(&mut self) -> (usize, T, Leaf<T, S>) {
+		self.split_biased(SplitBias::Middle)
+	}
+
+	#[inline]
+	pub fn split_biased(&mut self, bias: SplitBias) -> (usize, T, Leaf<T, S>) {
 		assert!(self.is_overflowing());
 
-		let median_i = (self.items.len() - 1) / 2;
+		let median_i = match bias {
+			SplitBias::Middle => (self.items.len() - 1) / 2,
+			// Leave `M / 2 - 1` items in the right leaf, the least it can hold
+			// without underflowing.
+			SplitBias::Right => self.items.len() - M / 2,
+		};
 
 		let right_items = self.items.drain(median_i + 1..).collect();
 		let median = self.items.pop().unwrap();
@@ -166,6 +186,15 @@ impl<T, S: Storage<T>> Leaf<T, S> {
 		(self.items.len(), median, right_leaf)
 	}
 
+	/// Moves the items from the given offset into a new leaf, without parent.
+	#[inline]
+	pub fn split_off(&mut self, offset: usize) -> Leaf<T, S> {
+		Leaf {
+			parent: None,
+			items: self.items.drain(offset..).collect(),
+		}
+	}
+
 	#[inline]
 	pub fn append(&mut self, separator: T, mut other: Leaf<T, S>) -> Offset {
 		let offset = self.items.len();
@@ -288,22 +317,30 @@ impl<T, S: Storage<T>> Leaf<T, S> {
 			}
 		}
 
-		if !self.items.windows(2).all(|w| cmp(&w[0], &w[1]).is_lt()) {
-			panic!("leaf items are not sorted")
+		for w in self.items.windows(2) {
+			match cmp(&w[0], &w[1]) {
+				Ordering::Less => (),
+				Ordering::Equal => panic!("leaf contains a duplicate key"),
+				Ordering::Greater => panic!("leaf items are not sorted"),
+			}
 		}
 
 		if let Some(min) = min {
 			if let Some(item) = self.items.first() {
-				if cmp(min, item).is_ge() {
-					panic!("leaf item key is greater than right separator")
+				match cmp(min, item) {
+					Ordering::Less => (),
+					Ordering::Equal => panic!("leaf item key is a duplicate of its separator"),
+					Ordering::Greater => panic!("leaf item key is greater than right separator"),
 				}
 			}
 		}
 
 		if let Some(max) = max {
 			if let Some(item) = self.items.last() {
-				if cmp(max, item).is_le() {
-					panic!("leaf item key is less than left separator")
+				match cmp(max, item) {
+					Ordering::Greater => (),
+					Ordering::Equal => panic!("leaf item key is a duplicate of its separator"),
+					Ordering::Less => panic!("leaf item key is less than left separator"),
 				}
 			}
 		}
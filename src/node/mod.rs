@@ -1,4 +1,4 @@
-use std::{cmp::Ordering, fmt};
+use std::{cmp::Ordering, fmt, ops::ControlFlow};
 
 mod addr;
 pub mod internal;
@@ -10,37 +10,83 @@ pub use leaf::Leaf as LeafNode;
 
 use crate::Storage;
 
+/// Integer type used to represent node offsets.
+///
+/// With the `small-offset` feature, offsets are stored in a `u16`, which
+/// reduces the size of addresses.
+#[cfg(not(feature = "small-offset"))]
+type OffsetRepr = usize;
+
+#[cfg(feature = "small-offset")]
+type OffsetRepr = u16;
+
+// The maximum value is reserved for the "before" offset.
+#[cfg(feature = "small-offset")]
+const _: () = assert!(crate::M < u16::MAX as usize);
+
 /// Offset in a node.
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
-pub struct Offset(usize);
+pub struct Offset(OffsetRepr);
 
 impl Offset {
+	#[allow(clippy::unnecessary_cast)]
+	#[inline]
+	fn from_usize(offset: usize) -> Offset {
+		// Also checked in release mode, where the cast would silently
+		// truncate the offset with the `small-offset` feature.
+		assert!(offset < OffsetRepr::MAX as usize, "offset out of bounds");
+		Offset(offset as OffsetRepr)
+	}
+
+	#[allow(clippy::unnecessary_cast)]
+	#[inline]
+	fn as_usize(self) -> usize {
+		self.0 as usize
+	}
+
 	pub fn before() -> Offset {
-		Offset(usize::MAX)
+		Offset(OffsetRepr::MAX)
 	}
 
 	pub fn is_before(&self) -> bool {
-		self.0 == usize::MAX
+		self.0 == OffsetRepr::MAX
 	}
 
 	pub fn value(&self) -> Option<usize> {
-		if self.0 == usize::MAX {
+		if self.0 == OffsetRepr::MAX {
 			None
 		} else {
-			Some(self.0)
+			Some(self.as_usize())
 		}
 	}
 
 	pub fn unwrap(self) -> usize {
-		if self.0 == usize::MAX {
+		if self.0 == OffsetRepr::MAX {
 			panic!("Offset out of bounds")
 		} else {
-			self.0
+			self.as_usize()
 		}
 	}
 
+	/// Converts the offset into an item index in the node.
+	///
+	/// Returns `None` for the "before" offset (`-1`).
+	#[inline]
+	pub fn to_index(self) -> Option<usize> {
+		self.value()
+	}
+
+	/// Creates an offset from an item index in the node.
+	///
+	/// Panics if the index is not less than the representation's maximum,
+	/// which is reserved for the "before" offset.
+	#[inline]
+	pub fn from_index(index: usize) -> Offset {
+		Self::from_usize(index)
+	}
+
 	pub fn incr(&mut self) {
-		if self.0 == usize::MAX {
+		if self.0 == OffsetRepr::MAX {
 			self.0 = 0
 		} else {
 			self.0 += 1
@@ -49,7 +95,7 @@ impl Offset {
 
 	pub fn decr(&mut self) {
 		if self.0 == 0 {
-			self.0 = usize::MAX
+			self.0 = OffsetRepr::MAX
 		} else {
 			self.0 -= 1
 		}
@@ -64,10 +110,10 @@ impl PartialOrd for Offset {
 
 impl Ord for Offset {
 	fn cmp(&self, offset: &Offset) -> Ordering {
-		if self.0 == usize::MAX || offset.0 == usize::MAX {
-			if self.0 == usize::MAX && offset.0 == usize::MAX {
+		if self.0 == OffsetRepr::MAX || offset.0 == OffsetRepr::MAX {
+			if self.0 == OffsetRepr::MAX && offset.0 == OffsetRepr::MAX {
 				Ordering::Equal
-			} else if self.0 == usize::MAX {
+			} else if self.0 == OffsetRepr::MAX {
 				Ordering::Less
 			} else {
 				Ordering::Greater
@@ -80,29 +126,29 @@ impl Ord for Offset {
 
 impl PartialEq<usize> for Offset {
 	fn eq(&self, offset: &usize) -> bool {
-		self.0 != usize::MAX && self.0 == *offset
+		self.0 != OffsetRepr::MAX && self.as_usize() == *offset
 	}
 }
 
 impl PartialOrd<usize> for Offset {
 	fn partial_cmp(&self, offset: &usize) -> Option<Ordering> {
-		if self.0 == usize::MAX {
+		if self.0 == OffsetRepr::MAX {
 			Some(Ordering::Less)
 		} else {
-			self.0.partial_cmp(offset)
+			(self.as_usize()).partial_cmp(offset)
 		}
 	}
 }
 
 impl From<usize> for Offset {
 	fn from(offset: usize) -> Offset {
-		Offset(offset)
+		Offset::from_usize(offset)
 	}
 }
 
 impl fmt::Display for Offset {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		if self.0 == usize::MAX {
+		if self.0 == OffsetRepr::MAX {
 			write!(f, "-1")
 		} else {
 			self.0.fmt(f)
@@ -112,7 +158,7 @@ impl fmt::Display for Offset {
 
 impl fmt::Debug for Offset {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		if self.0 == usize::MAX {
+		if self.0 == OffsetRepr::MAX {
 			write!(f, "-1")
 		} else {
 			self.0.fmt(f)
@@ -135,6 +181,20 @@ pub enum Balance {
 	Underflow(bool),
 }
 
+/// Node split strategy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitBias {
+	/// Split the node in the middle.
+	Middle,
+
+	/// Keep as many items as possible in the left node, leaving the new right
+	/// node with the minimum number of items.
+	///
+	/// This is best when items are appended at the end of the node, since
+	/// the left node will not receive new items.
+	Right,
+}
+
 /// Error returned when an operation on the node would result in an underflow.
 pub struct WouldUnderflow;
 
@@ -148,7 +208,7 @@ pub type LeftItem<T, S> = (T, Option<<S as Storage<T>>::Node>);
 pub type RightItem<T, S> = (Offset, T, Option<<S as Storage<T>>::Node>);
 
 /// B-tree node.
-// #[derive(Clone)]
+#[derive(Clone)]
 pub enum Node<T, S: Storage<T>> {
 	/// Internal node.
 	Internal(InternalNode<T, S>),
@@ -168,6 +228,12 @@ impl<T, S: Storage<T>> Node<T, S> {
 		Node::Leaf(LeafNode::from_item(parent, item))
 	}
 
+	/// Checks if this node is a leaf node.
+	#[inline]
+	pub fn is_leaf(&self) -> bool {
+		matches!(self, Node::Leaf(_))
+	}
+
 	#[inline]
 	pub fn balance(&self) -> Balance {
 		match self {
@@ -320,6 +386,43 @@ impl<T, S: Storage<T>> Node<T, S> {
 		}
 	}
 
+	/// Moves the items from the given offset, and the children following
+	/// them, into a new node without parent.
+	///
+	/// This node keeps its children up to the one at index `offset`. The
+	/// first child of the new node is `first_child`, which must be `Some`
+	/// for internal nodes.
+	#[inline]
+	pub fn split_off(&mut self, offset: usize, first_child: Option<S::Node>) -> Self {
+		match self {
+			Node::Internal(node) => Node::Internal(node.split_off(offset, first_child.unwrap())),
+			Node::Leaf(leaf) => Node::Leaf(leaf.split_off(offset)),
+		}
+	}
+
+	/// Returns an iterator over mutable references to the children
+	/// identifiers.
+	#[inline]
+	pub fn children_mut(&mut self) -> impl Iterator<Item = &mut S::Node> {
+		match self {
+			Node::Internal(node) => Some(node.children_mut()),
+			Node::Leaf(_) => None,
+		}
+		.into_iter()
+		.flatten()
+	}
+
+	/// Swaps the items at the given offsets.
+	///
+	/// Panics if an offset is out of bounds.
+	#[inline]
+	pub fn swap_items(&mut self, a: Offset, b: Offset) {
+		match self {
+			Node::Internal(node) => node.swap_items(a, b),
+			Node::Leaf(leaf) => leaf.swap_items(a, b),
+		}
+	}
+
 	/// Insert by key.
 	///
 	/// It is assumed that the node is not free.
@@ -343,13 +446,20 @@ impl<T, S: Storage<T>> Node<T, S> {
 	/// Return the length of the node after split, the median item and the right node.
 	#[inline]
 	pub fn split(&mut self) -> (usize, T, Node<T, S>) {
+		self.split_biased(SplitBias::Middle)
+	}
+
+	/// Split the node using the given strategy.
+	/// Return the length of the node after split, the median item and the right node.
+	#[inline]
+	pub fn split_biased(&mut self, bias: SplitBias) -> (usize, T, Node<T, S>) {
 		match self {
 			Node::Internal(node) => {
-				let (len, item, right_node) = node.split();
+				let (len, item, right_node) = node.split_biased(bias);
 				(len, item, Node::Internal(right_node))
 			}
 			Node::Leaf(leaf) => {
-				let (len, item, right_leaf) = leaf.split();
+				let (len, item, right_leaf) = leaf.split_biased(bias);
 				(len, item, Node::Leaf(right_leaf))
 			}
 		}
@@ -474,8 +584,17 @@ impl<T, S: Storage<T>> Node<T, S> {
 		}
 	}
 
+	/// Returns an iterator over mutable references to the items of the node.
 	#[inline]
-	pub fn children(&self) -> Children<T, S> {
+	pub fn items_mut(&mut self) -> ItemsMut<'_, T, S> {
+		match self {
+			Node::Leaf(leaf) => ItemsMut::Leaf(leaf.iter_mut()),
+			Node::Internal(node) => ItemsMut::Internal(node.branches_iter_mut()),
+		}
+	}
+
+	#[inline]
+	pub fn children(&self) -> Children<'_, T, S> {
 		match self {
 			Node::Leaf(_) => Children::Leaf,
 			Node::Internal(node) => node.children(),
@@ -483,7 +602,7 @@ impl<T, S: Storage<T>> Node<T, S> {
 	}
 
 	#[inline]
-	pub fn children_with_separators(&self) -> ChildrenWithSeparators<T, S> {
+	pub fn children_with_separators(&self) -> ChildrenWithSeparators<'_, T, S> {
 		match self {
 			Node::Leaf(_) => ChildrenWithSeparators::Leaf,
 			Node::Internal(node) => node.children_with_separators(),
@@ -504,6 +623,30 @@ impl<T, S: Storage<T>> Node<T, S> {
 		}
 	}
 
+	pub fn try_visit_from_leaves<B>(
+		&self,
+		nodes: &S,
+		mut f: impl FnMut(S::Node) -> ControlFlow<B>,
+	) -> ControlFlow<B> {
+		self.try_visit_from_leaves_with(nodes, &mut f)
+	}
+
+	pub fn try_visit_from_leaves_with<B>(
+		&self,
+		nodes: &S,
+		f: &mut impl FnMut(S::Node) -> ControlFlow<B>,
+	) -> ControlFlow<B> {
+		if let Node::Internal(node) = self {
+			for c in node.children() {
+				let child = unsafe { nodes.get(c) };
+				child.try_visit_from_leaves_with(nodes, f)?;
+				f(c)?;
+			}
+		}
+
+		ControlFlow::Continue(())
+	}
+
 	pub fn visit_from_leaves_mut(&self, nodes: &mut S, mut f: impl FnMut(S::Node, &mut Self)) {
 		self.visit_from_leaves_mut_with(nodes, &mut f)
 	}
@@ -585,6 +728,23 @@ impl<'a, T, S: Storage<T>> Iterator for Children<'a, T, S> {
 	}
 }
 
+pub enum ItemsMut<'a, T, S: Storage<T>> {
+	Leaf(std::slice::IterMut<'a, T>),
+	Internal(std::slice::IterMut<'a, internal::Branch<T, S>>),
+}
+
+impl<'a, T, S: Storage<T>> Iterator for ItemsMut<'a, T, S> {
+	type Item = &'a mut T;
+
+	#[inline]
+	fn next(&mut self) -> Option<&'a mut T> {
+		match self {
+			ItemsMut::Leaf(items) => items.next(),
+			ItemsMut::Internal(branches) => branches.next().map(|branch| &mut branch.item),
+		}
+	}
+}
+
 pub enum ChildrenWithSeparators<'a, T, S: Storage<T>> {
 	Leaf,
 	Internal(
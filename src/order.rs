@@ -0,0 +1,66 @@
+use std::cmp::Ordering;
+
+/// Total order property violated by a comparison function.
+///
+/// Returned by [`check_total_order`]. Items are given by their index in the
+/// checked sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderViolation {
+	/// The item doesn't compare equal to itself.
+	Reflexivity(usize),
+
+	/// Comparing `a` with `b` doesn't give the reverse of comparing `b`
+	/// with `a`.
+	Antisymmetry(usize, usize),
+
+	/// `a <= b` and `b <= c`, but not `a <= c` (or not `a == c` when both
+	/// are equalities).
+	Transitivity(usize, usize, usize),
+}
+
+/// Checks that the given comparison function is a total order over the given
+/// sample of items.
+///
+/// Reflexivity, antisymmetry and transitivity are checked for every item,
+/// pair and triple of the sample, returning the first violation found. This
+/// is cubic in the size of the sample, and meant to be used in tests.
+pub fn check_total_order<T>(
+	cmp: impl Fn(&T, &T) -> Ordering,
+	sample: &[T],
+) -> Result<(), OrderViolation> {
+	for (i, a) in sample.iter().enumerate() {
+		if cmp(a, a).is_ne() {
+			return Err(OrderViolation::Reflexivity(i));
+		}
+	}
+
+	for (i, a) in sample.iter().enumerate() {
+		for (j, b) in sample.iter().enumerate() {
+			if cmp(a, b) != cmp(b, a).reverse() {
+				return Err(OrderViolation::Antisymmetry(i, j));
+			}
+		}
+	}
+
+	for (i, a) in sample.iter().enumerate() {
+		for (j, b) in sample.iter().enumerate() {
+			let ab = cmp(a, b);
+			if ab.is_gt() {
+				continue;
+			}
+
+			for (k, c) in sample.iter().enumerate() {
+				let bc = cmp(b, c);
+				if bc.is_gt() {
+					continue;
+				}
+
+				if cmp(a, c) != ab.then(bc) {
+					return Err(OrderViolation::Transitivity(i, j, k));
+				}
+			}
+		}
+	}
+
+	Ok(())
+}
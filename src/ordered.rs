@@ -0,0 +1,153 @@
+use crate::{storage::BoxStorage, Iter, RawBTree, Storage};
+use std::cmp::Ordering;
+
+/// B-Tree storing its comparison function.
+///
+/// Forwards operations to a [`RawBTree`], always using the same comparison
+/// function, so that the tree cannot be accidentally searched or modified
+/// with an inconsistent order. Items are compared with other items only.
+///
+/// # Example
+///
+/// ```
+/// use raw_btree::OrderedTree;
+///
+/// let mut tree = OrderedTree::new(|a: &i32, b: &i32| b.cmp(a));
+/// tree.insert(1);
+/// tree.insert(3);
+/// tree.insert(2);
+///
+/// assert!(tree.iter().copied().eq([3, 2, 1]));
+/// assert_eq!(tree.get(&2), Some(&2));
+/// ```
+pub struct OrderedTree<T, F, S: Storage<T> = BoxStorage> {
+	/// Underlying tree.
+	tree: RawBTree<T, S>,
+
+	/// Comparison function.
+	cmp: F,
+}
+
+impl<T, F: Fn(&T, &T) -> Ordering> OrderedTree<T, F> {
+	/// Creates a new empty tree ordered by the given comparison function.
+	#[inline]
+	pub fn new(cmp: F) -> Self {
+		Self::with_storage(BoxStorage, cmp)
+	}
+}
+
+impl<T, F: Fn(&T, &T) -> Ordering, S: Storage<T>> OrderedTree<T, F, S> {
+	/// Creates a new empty tree using the given node storage, ordered by the
+	/// given comparison function.
+	#[inline]
+	pub fn with_storage(nodes: S, cmp: F) -> Self {
+		Self {
+			tree: RawBTree::with_storage(nodes),
+			cmp,
+		}
+	}
+
+	/// Returns the underlying tree.
+	#[inline]
+	pub fn as_raw(&self) -> &RawBTree<T, S> {
+		&self.tree
+	}
+
+	/// Returns the underlying tree and the comparison function.
+	#[inline]
+	pub fn into_raw(self) -> (RawBTree<T, S>, F) {
+		(self.tree, self.cmp)
+	}
+
+	/// Returns the comparison function.
+	#[inline]
+	pub fn comparator(&self) -> &F {
+		&self.cmp
+	}
+
+	#[inline]
+	pub fn len(&self) -> usize {
+		self.tree.len()
+	}
+
+	#[inline]
+	pub fn is_empty(&self) -> bool {
+		self.tree.is_empty()
+	}
+
+	#[inline]
+	pub fn get(&self, item: &T) -> Option<&T> {
+		self.tree.get(&self.cmp, item)
+	}
+
+	#[inline]
+	pub fn contains(&self, item: &T) -> bool {
+		self.get(item).is_some()
+	}
+
+	#[inline]
+	pub fn first(&self) -> Option<&T> {
+		self.tree.first()
+	}
+
+	#[inline]
+	pub fn last(&self) -> Option<&T> {
+		self.tree.last()
+	}
+
+	/// Inserts the given item, returning the item it replaces, if any.
+	#[inline]
+	pub fn insert(&mut self, item: T) -> Option<T> {
+		self.tree.insert(&self.cmp, item)
+	}
+
+	/// Removes the item comparing equal to the given one, and returns it.
+	#[inline]
+	pub fn remove(&mut self, item: &T) -> Option<T> {
+		self.tree.remove(&self.cmp, item)
+	}
+
+	#[inline]
+	pub fn pop_first(&mut self) -> Option<T> {
+		self.tree.pop_first()
+	}
+
+	#[inline]
+	pub fn pop_last(&mut self) -> Option<T> {
+		self.tree.pop_last()
+	}
+
+	#[inline]
+	pub fn clear(&mut self) {
+		self.tree.clear()
+	}
+
+	#[inline]
+	pub fn iter(&self) -> Iter<'_, T, S> {
+		self.tree.iter()
+	}
+
+	#[cfg(debug_assertions)]
+	pub fn validate(&self) {
+		self.tree.validate(&self.cmp)
+	}
+}
+
+impl<T, F: Fn(&T, &T) -> Ordering, S: Storage<T>> Extend<T> for OrderedTree<T, F, S> {
+	#[inline]
+	fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+		for item in iter {
+			self.insert(item);
+		}
+	}
+}
+
+impl<'a, T, F: Fn(&T, &T) -> Ordering, S: Storage<T>> IntoIterator for &'a OrderedTree<T, F, S> {
+	type IntoIter = Iter<'a, T, S>;
+	type Item = &'a T;
+
+	#[inline]
+	fn into_iter(self) -> Iter<'a, T, S> {
+		self.iter()
+	}
+}
@@ -0,0 +1,44 @@
+use std::borrow::Borrow;
+
+/// Key that can be used to search items by prefix.
+///
+/// Used by [`RawBTree::range_prefix`](crate::RawBTree::range_prefix).
+pub trait PrefixKey: ToOwned {
+	/// Returns the smallest key greater than any key starting with `self`.
+	///
+	/// Returns `None` if there is no such key, meaning that every key
+	/// greater than `self` starts with `self`.
+	fn prefix_successor(&self) -> Option<Self::Owned>
+	where
+		Self::Owned: Borrow<Self>;
+}
+
+impl PrefixKey for [u8] {
+	fn prefix_successor(&self) -> Option<Vec<u8>> {
+		// Trailing `0xff` bytes cannot be incremented.
+		let len = self.iter().rposition(|b| *b != 0xff)? + 1;
+		let mut result = self[..len].to_vec();
+		result[len - 1] += 1;
+		Some(result)
+	}
+}
+
+impl PrefixKey for str {
+	fn prefix_successor(&self) -> Option<String> {
+		let mut result = self.to_owned();
+		while let Some(c) = result.pop() {
+			// Skip the surrogate range.
+			let next = match c {
+				'\u{d7ff}' => Some('\u{e000}'),
+				_ => char::from_u32(c as u32 + 1),
+			};
+
+			if let Some(next) = next {
+				result.push(next);
+				return Some(result);
+			}
+		}
+
+		None
+	}
+}
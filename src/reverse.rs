@@ -0,0 +1,42 @@
+use std::cmp::Ordering;
+
+/// Item wrapper reversing the order of the items.
+///
+/// Using [`reverse_cmp`] as comparison function, the first item of a tree of
+/// `Reverse<T>` is the greatest one.
+///
+/// # Example
+///
+/// Using the tree as a max-priority queue:
+///
+/// ```
+/// use raw_btree::{reverse_cmp, RawBTree, Reverse};
+///
+/// let mut queue: RawBTree<Reverse<u32>> = RawBTree::new();
+/// for priority in [3, 7, 1, 5] {
+///     queue.insert(reverse_cmp, Reverse(priority));
+/// }
+///
+/// assert_eq!(queue.pop_first(), Some(Reverse(7)));
+/// assert_eq!(queue.pop_first(), Some(Reverse(5)));
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Reverse<T>(pub T);
+
+impl<T: PartialOrd> PartialOrd for Reverse<T> {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		other.0.partial_cmp(&self.0)
+	}
+}
+
+impl<T: Ord> Ord for Reverse<T> {
+	fn cmp(&self, other: &Self) -> Ordering {
+		other.0.cmp(&self.0)
+	}
+}
+
+/// Compares two reversed items, in the reverse order of the wrapped items.
+#[inline]
+pub fn reverse_cmp<T: Ord>(a: &Reverse<T>, b: &Reverse<T>) -> Ordering {
+	b.0.cmp(&a.0)
+}
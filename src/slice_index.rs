@@ -0,0 +1,144 @@
+use crate::RawBTree;
+use std::cmp::Ordering;
+
+/// Index over a borrowed slice.
+///
+/// Stores indices into the slice in a [`RawBTree`], ordered by comparing
+/// the records they point to with a single comparator. The comparator is
+/// bundled with the slice so that it is consistently applied to every
+/// operation.
+///
+/// Records comparing equal are considered to be the same key: inserting the
+/// index of a record equal to an already indexed one replaces the previous
+/// index.
+///
+/// # Example
+///
+/// ```
+/// use raw_btree::SliceIndex;
+///
+/// struct Record {
+///     name: &'static str,
+///     age: u32,
+/// }
+///
+/// let records = [
+///     Record { name: "b", age: 30 },
+///     Record { name: "c", age: 20 },
+///     Record { name: "a", age: 40 },
+/// ];
+///
+/// let by_name = SliceIndex::from_all(&records, |a, b| a.name.cmp(b.name));
+/// let names: Vec<_> = by_name.iter().map(|(_, r)| r.name).collect();
+/// assert_eq!(names, ["a", "b", "c"]);
+///
+/// let (i, record) = by_name.get(|r, name| r.name.cmp(name), "c").unwrap();
+/// assert_eq!((i, record.age), (1, 20));
+/// ```
+pub struct SliceIndex<'a, R, C> {
+	/// Indexed records.
+	data: &'a [R],
+
+	/// Record comparator.
+	cmp: C,
+
+	/// Indices, ordered by record.
+	tree: RawBTree<usize>,
+}
+
+impl<'a, R, C: Fn(&R, &R) -> Ordering> SliceIndex<'a, R, C> {
+	/// Creates a new empty index over the given slice.
+	#[inline]
+	pub fn new(data: &'a [R], cmp: C) -> Self {
+		Self {
+			data,
+			cmp,
+			tree: RawBTree::new(),
+		}
+	}
+
+	/// Creates a new index over all the records of the given slice.
+	pub fn from_all(data: &'a [R], cmp: C) -> Self {
+		let mut result = Self::new(data, cmp);
+		for i in 0..data.len() {
+			result.insert(i);
+		}
+
+		result
+	}
+
+	/// Returns the indexed slice.
+	#[inline]
+	pub fn data(&self) -> &'a [R] {
+		self.data
+	}
+
+	/// Returns the underlying tree of indices.
+	#[inline]
+	pub fn tree(&self) -> &RawBTree<usize> {
+		&self.tree
+	}
+
+	#[inline]
+	pub fn len(&self) -> usize {
+		self.tree.len()
+	}
+
+	#[inline]
+	pub fn is_empty(&self) -> bool {
+		self.tree.is_empty()
+	}
+
+	/// Indexes the record at position `i` in the slice.
+	///
+	/// If a record comparing equal was already indexed, its index is
+	/// replaced and returned.
+	///
+	/// # Panics
+	///
+	/// Panics if `i` is out of the slice bounds.
+	pub fn insert(&mut self, i: usize) -> Option<usize> {
+		assert!(i < self.data.len());
+		let data = self.data;
+		let cmp = &self.cmp;
+		self.tree.insert(|a, b| cmp(&data[*a], &data[*b]), i)
+	}
+
+	/// Removes the record at position `i` in the slice from the index.
+	///
+	/// Returns the index that was removed, which is the index of a record
+	/// comparing equal to the record at position `i`.
+	///
+	/// # Panics
+	///
+	/// Panics if `i` is out of the slice bounds.
+	pub fn remove(&mut self, i: usize) -> Option<usize> {
+		assert!(i < self.data.len());
+		let data = self.data;
+		let cmp = &self.cmp;
+		self.tree
+			.remove(|a, b: &usize| cmp(&data[*a], &data[*b]), &i)
+	}
+
+	/// Finds the record matching the given key, and returns it along with its
+	/// position in the slice.
+	///
+	/// The key comparator must be consistent with the record comparator.
+	pub fn get<Q: ?Sized>(
+		&self,
+		cmp: impl Fn(&R, &Q) -> Ordering,
+		key: &Q,
+	) -> Option<(usize, &'a R)> {
+		let data = self.data;
+		self.tree
+			.get(|i, key| cmp(&data[*i], key), key)
+			.map(|i| (*i, &data[*i]))
+	}
+
+	/// Returns an iterator over the indexed records, in order, along with
+	/// their position in the slice.
+	pub fn iter(&self) -> impl Iterator<Item = (usize, &'a R)> + '_ {
+		let data = self.data;
+		self.tree.iter().map(move |i| (*i, &data[*i]))
+	}
+}
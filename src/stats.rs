@@ -0,0 +1,81 @@
+use crate::RebalanceEvent;
+
+/// Tree statistics.
+///
+/// Returned by [`RawBTree::stats`](crate::RawBTree::stats).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TreeStats {
+	/// Number of items in the tree.
+	pub len: usize,
+
+	/// Number of levels in the tree.
+	///
+	/// Zero for an empty tree.
+	pub height: usize,
+
+	/// Number of nodes.
+	pub node_count: usize,
+
+	/// Number of leaf nodes.
+	pub leaf_count: usize,
+
+	/// Number of internal nodes.
+	pub internal_count: usize,
+
+	/// Minimum number of items in a node, if any.
+	pub min_occupancy: Option<usize>,
+
+	/// Maximum number of items in a node, if any.
+	pub max_occupancy: Option<usize>,
+
+	/// Average number of items in a node, if any.
+	pub avg_occupancy: Option<f64>,
+}
+
+/// Number of structural operations performed while rebalancing a tree.
+///
+/// Accumulated by the counters set with `RawBTree::with_rebalance_counters`,
+/// available with the `rebalance-observer` feature.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RebalanceCounters {
+	/// Number of node splits.
+	pub splits: usize,
+
+	/// Number of node merges.
+	pub merges: usize,
+
+	/// Number of rotations between siblings.
+	pub rotations: usize,
+}
+
+impl RebalanceCounters {
+	/// Counts the given event.
+	pub fn record<N>(&mut self, event: &RebalanceEvent<N>) {
+		match event {
+			RebalanceEvent::Split { .. } => self.splits += 1,
+			RebalanceEvent::Merge { .. } => self.merges += 1,
+			RebalanceEvent::RotateLeft { .. } | RebalanceEvent::RotateRight { .. } => {
+				self.rotations += 1
+			}
+		}
+	}
+}
+
+/// Locality of the nodes visited by an in-order scan of a tree.
+///
+/// Returned by [`RawBTree::scan_locality`](crate::RawBTree::scan_locality).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LocalityStats {
+	/// Number of times the scan moves from a node to another.
+	pub transitions: usize,
+
+	/// Number of transitions to a node with a greater address.
+	pub forward: usize,
+
+	/// Number of transitions to a node with a lower address.
+	pub backward: usize,
+
+	/// Average absolute difference between the addresses of consecutive
+	/// nodes, if any transition.
+	pub avg_distance: Option<f64>,
+}
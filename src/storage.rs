@@ -1,11 +1,11 @@
 use crate::{
-	balancing::rebalance,
+	balancing::{insert_exactly_at_observed, remove_at_observed},
 	node::{Address, Offset},
 	utils::Array,
 	Node, M,
 };
 use core::fmt;
-use std::{cmp::Ordering, ptr::NonNull};
+use std::{cmp::Ordering, ptr::NonNull, sync::Arc};
 
 /// BTree node storage.
 ///
@@ -15,7 +15,8 @@ use std::{cmp::Ordering, ptr::NonNull};
 /// created using `allocate_node` (or `insert_node`) but not yet released using
 /// `release_node` or a `Dropper` (created with `start_dropping`).
 ///
-/// - Default method implementations must not be overridden by the implementor.
+/// - Default method implementations must not be overridden by the implementor,
+///   except for `reserve`, `live_nodes_mut` and `share`.
 /// - `allocate_node` must not return an *active* identifier.
 ///   Once returned and until released using `release_node`, this identifier
 ///   must always map to the same node through `get` and `get_mut`.
@@ -23,19 +24,54 @@ use std::{cmp::Ordering, ptr::NonNull};
 ///   The created node must live at least as long as its identifier is active
 ///   and the storage is not dropped.
 /// - `release_node` may only drop the node bound to the given identifier.
+/// - `empty` must return a storage without *active* identifiers.
 /// - `start_dropping` creates a dropper for this storage.
 /// - `get` must return the node bound to the given identifier.
 /// - `get_mut` must return the node bound to the given identifier.
-pub unsafe trait Storage<T>: Default {
+/// - `live_nodes_mut` must return `None` or yield every node bound to an
+///   *active* identifier exactly once, as `get_mut` would.
+pub unsafe trait Storage<T>: Sized {
 	/// Node.
 	type Node: Copy + PartialEq + core::fmt::Debug;
 
 	/// Nodes dropper.
 	type Dropper: Dropper<T, Self>;
 
+	/// Creates a new empty storage, with the same configuration as this one.
+	fn empty(&self) -> Self;
+
 	/// Allocates the given node.
 	fn allocate_node(&mut self, node: Node<T, Self>) -> Self::Node;
 
+	/// Reserves capacity for at least `additional` more nodes.
+	///
+	/// Does nothing by default.
+	fn reserve(&mut self, additional: usize) {
+		let _ = additional;
+	}
+
+	/// Returns an iterator over every active node, in storage order, if
+	/// supported.
+	///
+	/// Visiting nodes in storage order may be more cache-friendly than
+	/// following their identifiers. Returns `None` by default, meaning that
+	/// nodes can only be visited through their identifiers.
+	fn live_nodes_mut<'a>(&'a mut self) -> Option<impl Iterator<Item = &'a mut Node<T, Self>>>
+	where
+		T: 'a,
+	{
+		None::<std::iter::Empty<_>>
+	}
+
+	/// Returns a storage sharing the nodes of this one, without copying
+	/// them.
+	///
+	/// Mutating a node through either storage must not affect the other.
+	/// Returns `None` by default, meaning that nodes can't be shared.
+	fn share(&self) -> Option<Self> {
+		None
+	}
+
 	/// # Safety
 	///
 	/// Input node must not have been deallocated.
@@ -402,22 +438,7 @@ pub unsafe trait Storage<T>: Default {
 		item: T,
 		opt_right_id: Option<Self::Node>,
 	) -> (Option<Self::Node>, Option<Address<Self::Node>>) {
-		match addr {
-			Some(addr) => {
-				self.get_mut(addr.node)
-					.insert(addr.offset, item, opt_right_id);
-				rebalance(self, root, addr.node, addr)
-			}
-			None => {
-				let new_root = Node::leaf(None, item);
-				let id = self.insert_node(new_root);
-				let addr = Address {
-					node: id,
-					offset: 0.into(),
-				};
-				(Some(id), Some(addr))
-			}
-		}
+		insert_exactly_at_observed(self, root, addr, item, opt_right_id, None)
 	}
 
 	/// Replaces the item located at the given address.
@@ -438,30 +459,7 @@ pub unsafe trait Storage<T>: Default {
 		root: Option<Self::Node>,
 		addr: Address<Self::Node>,
 	) -> Option<RemovedItem<T, Self>> {
-		match self.get_mut(addr.node).leaf_remove(addr.offset) {
-			Some(Ok(item)) => {
-				// removed from a leaf.
-				let (new_root, new_addr) = rebalance(self, root, addr.node, addr);
-				Some(RemovedItem {
-					new_root,
-					item,
-					new_addr,
-				})
-			}
-			Some(Err(left_child_id)) => {
-				// removed from an internal node.
-				let new_addr = self.next_item_or_back_address(addr).unwrap();
-				let (separator, leaf_id) = self.remove_rightmost_leaf_of(left_child_id);
-				let item = self.get_mut(addr.node).replace(addr.offset, separator);
-				let (new_root, new_addr) = rebalance(self, root, leaf_id, new_addr);
-				Some(RemovedItem {
-					new_root,
-					item,
-					new_addr,
-				})
-			}
-			None => None,
-		}
+		remove_at_observed(self, root, addr, None)
 	}
 
 	/// Remove the rightmost leaf node under the given node.
@@ -511,6 +509,10 @@ pub struct BoxStorage;
 
 pub struct BoxPtr<T>(NonNull<Node<T, BoxStorage>>); // TODO use `core::ptr::Unique` when it is stable.
 
+// Each node is uniquely owned by the tree holding its pointer, like a `Box`,
+// and is only mutably dereferenced through `&mut BoxStorage`. It is then
+// `Send` and `Sync` under the same conditions as `Box<Node<T>>`, which makes
+// `RawBTree<T, BoxStorage>` `Send` when `T: Send` and `Sync` when `T: Sync`.
 unsafe impl<T: Send> Send for BoxPtr<T> {}
 unsafe impl<T: Sync> Sync for BoxPtr<T> {}
 
@@ -519,6 +521,10 @@ unsafe impl<T> Storage<T> for BoxStorage {
 
 	type Dropper = BoxDrop;
 
+	fn empty(&self) -> Self {
+		BoxStorage
+	}
+
 	fn allocate_node(&mut self, node: Node<T, Self>) -> Self::Node {
 		let b = Box::new(node);
 		BoxPtr(NonNull::new(Box::into_raw(b)).unwrap())
@@ -577,3 +583,288 @@ unsafe impl<T> Dropper<T, BoxStorage> for BoxDrop {
 		let _ = Box::from_raw(id.0.as_ptr());
 	}
 }
+
+/// Copy-on-write storage.
+///
+/// Nodes are reference counted so that cloning the storage (and a tree using
+/// it) is O(1). A node shared with a clone is only copied the first time it
+/// is mutably accessed through [`Storage::get_mut`], along with its shared
+/// ancestors. Node identifiers are indexes in a persistent table that remain
+/// the same across clones, so that parent and child identifiers stored in
+/// the nodes stay valid. Only the table chunks leading to a copied node are
+/// copied with it, never the whole table.
+///
+/// Since ancestors are copied along with their descendants, a node shared
+/// with a clone roots a subtree entirely shared with this clone.
+pub struct CowStorage<T: Clone> {
+	/// Root chunk of the node table.
+	root: Option<Arc<CowChunk<T>>>,
+
+	/// Height of the root chunk, leaf chunks being at height `0`.
+	height: usize,
+
+	/// Number of slots in the table.
+	len: usize,
+
+	/// First free slot, available for reuse.
+	free: Option<usize>,
+}
+
+/// Shared copy-on-write node.
+type CowNode<T> = Arc<Node<T, CowStorage<T>>>;
+
+/// Number of bits of a node identifier used to index a chunk.
+const COW_CHUNK_BITS: usize = 5;
+
+/// Number of entries in a chunk.
+const COW_CHUNK_LEN: usize = 1 << COW_CHUNK_BITS;
+
+/// Node table slot.
+#[derive(Clone)]
+enum CowSlot<T: Clone> {
+	Used(CowNode<T>),
+
+	/// Free slot, with the next free slot.
+	Free(Option<usize>),
+}
+
+/// Node table chunk.
+#[derive(Clone)]
+enum CowChunk<T: Clone> {
+	Leaf(Vec<CowSlot<T>>),
+	Internal(Vec<Arc<CowChunk<T>>>),
+}
+
+impl<T: Clone> CowStorage<T> {
+	/// Returns the index of the given identifier in its chunk at the given
+	/// height.
+	#[inline]
+	fn chunk_index(id: usize, height: usize) -> usize {
+		(id >> (height * COW_CHUNK_BITS)) & (COW_CHUNK_LEN - 1)
+	}
+
+	fn slot(&self, id: usize) -> Option<&CowSlot<T>> {
+		if id >= self.len {
+			return None;
+		}
+
+		let mut chunk = self.root.as_deref()?;
+		for height in (1..=self.height).rev() {
+			match chunk {
+				CowChunk::Internal(chunks) => chunk = &chunks[Self::chunk_index(id, height)],
+				CowChunk::Leaf(_) => unreachable!(),
+			}
+		}
+
+		match chunk {
+			CowChunk::Leaf(slots) => slots.get(Self::chunk_index(id, 0)),
+			CowChunk::Internal(_) => unreachable!(),
+		}
+	}
+
+	/// Returns the given slot, copying the chunks leading to it if they are
+	/// shared.
+	fn slot_mut(&mut self, id: usize) -> &mut CowSlot<T> {
+		let mut chunk = Arc::make_mut(self.root.as_mut().unwrap());
+		for height in (1..=self.height).rev() {
+			match chunk {
+				CowChunk::Internal(chunks) => {
+					chunk = Arc::make_mut(&mut chunks[Self::chunk_index(id, height)])
+				}
+				CowChunk::Leaf(_) => unreachable!(),
+			}
+		}
+
+		match chunk {
+			CowChunk::Leaf(slots) => &mut slots[Self::chunk_index(id, 0)],
+			CowChunk::Internal(_) => unreachable!(),
+		}
+	}
+
+	/// Appends a new slot to the table.
+	fn push_slot(&mut self, slot: CowSlot<T>) -> usize {
+		let id = self.len;
+		match &mut self.root {
+			None => self.root = Some(Arc::new(CowChunk::Leaf(Vec::new()))),
+			Some(root) => {
+				if id == COW_CHUNK_LEN << (self.height * COW_CHUNK_BITS) {
+					let old_root = root.clone();
+					*root = Arc::new(CowChunk::Internal(vec![old_root]));
+					self.height += 1
+				}
+			}
+		}
+
+		let mut chunk = Arc::make_mut(self.root.as_mut().unwrap());
+		for height in (1..=self.height).rev() {
+			match chunk {
+				CowChunk::Internal(chunks) => {
+					let i = Self::chunk_index(id, height);
+					if i == chunks.len() {
+						chunks.push(Arc::new(if height == 1 {
+							CowChunk::Leaf(Vec::with_capacity(COW_CHUNK_LEN))
+						} else {
+							CowChunk::Internal(Vec::with_capacity(COW_CHUNK_LEN))
+						}))
+					}
+
+					chunk = Arc::make_mut(&mut chunks[i])
+				}
+				CowChunk::Leaf(_) => unreachable!(),
+			}
+		}
+
+		match chunk {
+			CowChunk::Leaf(slots) => slots.push(slot),
+			CowChunk::Internal(_) => unreachable!(),
+		}
+
+		self.len += 1;
+		id
+	}
+
+	/// Returns the given node, copying the chunks leading to it if they are
+	/// shared.
+	fn node_mut(&mut self, id: usize) -> &mut CowNode<T> {
+		match self.slot_mut(id) {
+			CowSlot::Used(node) => node,
+			CowSlot::Free(_) => panic!("released node"),
+		}
+	}
+
+	/// Checks if the given node is shared with the `other` storage, meaning
+	/// that it has not been copied since one storage was cloned from the
+	/// other.
+	///
+	/// A shared node holds the same items and child identifiers in both
+	/// storages, and so do all its descendants.
+	pub fn shares_node(&self, other: &Self, id: usize) -> bool {
+		match (self.slot(id), other.slot(id)) {
+			(Some(CowSlot::Used(a)), Some(CowSlot::Used(b))) => Arc::ptr_eq(a, b),
+			_ => false,
+		}
+	}
+}
+
+impl<T: Clone> Default for CowStorage<T> {
+	fn default() -> Self {
+		Self {
+			root: None,
+			height: 0,
+			len: 0,
+			free: None,
+		}
+	}
+}
+
+impl<T: Clone> Clone for CowStorage<T> {
+	fn clone(&self) -> Self {
+		Self {
+			root: self.root.clone(),
+			height: self.height,
+			len: self.len,
+			free: self.free,
+		}
+	}
+}
+
+unsafe impl<T: Clone> Storage<T> for CowStorage<T> {
+	type Node = usize;
+
+	type Dropper = CowDrop;
+
+	fn empty(&self) -> Self {
+		Self::default()
+	}
+
+	fn allocate_node(&mut self, node: Node<T, Self>) -> usize {
+		let slot = CowSlot::Used(Arc::new(node));
+		match self.free {
+			Some(id) => {
+				match std::mem::replace(self.slot_mut(id), slot) {
+					CowSlot::Free(next) => self.free = next,
+					CowSlot::Used(_) => unreachable!(),
+				}
+
+				id
+			}
+			None => self.push_slot(slot),
+		}
+	}
+
+	fn live_nodes_mut<'a>(&'a mut self) -> Option<impl Iterator<Item = &'a mut Node<T, Self>>>
+	where
+		T: 'a,
+	{
+		let this: *mut Self = self;
+		Some((0..self.len).filter_map(move |id| unsafe {
+			// Each node is visited once, so it is only mutably borrowed once.
+			// Copying a node (or its ancestors) doesn't move the nodes that
+			// have already been visited, since they are not shared.
+			let this = &mut *this;
+			match this.slot(id) {
+				Some(CowSlot::Used(_)) => Some(&mut *(this.get_mut(id) as *mut Node<T, Self>)),
+				_ => None,
+			}
+		}))
+	}
+
+	fn share(&self) -> Option<Self> {
+		Some(self.clone())
+	}
+
+	unsafe fn release_node(&mut self, id: usize) -> Node<T, Self> {
+		let free = self.free.replace(id);
+		match std::mem::replace(self.slot_mut(id), CowSlot::Free(free)) {
+			CowSlot::Used(node) => Arc::try_unwrap(node).unwrap_or_else(|node| (*node).clone()),
+			CowSlot::Free(_) => panic!("released node"),
+		}
+	}
+
+	fn start_dropping(&self) -> Option<CowDrop> {
+		// Dropping the storage drops the nodes.
+		None
+	}
+
+	unsafe fn get(&self, id: usize) -> &Node<T, Self> {
+		match self.slot(id) {
+			Some(CowSlot::Used(node)) => node,
+			_ => panic!("released node"),
+		}
+	}
+
+	unsafe fn get_mut(&mut self, id: usize) -> &mut Node<T, Self> {
+		// Nodes are never weakly referenced, so a node with a single strong
+		// reference is not shared.
+		let node = self.node_mut(id);
+		if Arc::strong_count(node) > 1 {
+			// Copy the node and its shared ancestors. Ancestors of a node
+			// that is not shared are not shared either, and may already be
+			// mutably borrowed, so they are not accessed.
+			let mut parent = Arc::make_mut(node).parent();
+			while let Some(parent_id) = parent {
+				match self.slot_mut(parent_id) {
+					CowSlot::Used(node) if Arc::strong_count(node) > 1 => {
+						parent = Arc::make_mut(node).parent()
+					}
+					// The parent is not shared, or has been released while
+					// rebalancing.
+					_ => break,
+				}
+			}
+		}
+
+		Arc::get_mut(self.node_mut(id)).unwrap()
+	}
+}
+
+/// Copy-on-write storage dropper.
+///
+/// Never created, since dropping a [`CowStorage`] drops its nodes.
+pub enum CowDrop {}
+
+unsafe impl<T: Clone> Dropper<T, CowStorage<T>> for CowDrop {
+	unsafe fn drop_node(&mut self, _id: usize) {
+		match *self {}
+	}
+}
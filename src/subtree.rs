@@ -0,0 +1,136 @@
+use crate::{storage::BoxStorage, Address, Iter, RawBTree, Storage};
+use std::cmp::Ordering;
+
+/// Read-only view of a subtree.
+///
+/// Behaves like a tree restricted to the items of the subtree, whose logical
+/// key range is delimited by the separators surrounding the subtree root in
+/// its ancestors.
+pub struct SubtreeView<'a, T, S: Storage<T> = BoxStorage> {
+	/// The tree reference.
+	btree: &'a RawBTree<T, S>,
+
+	/// Root of the subtree.
+	root: S::Node,
+}
+
+impl<'a, T, S: Storage<T>> SubtreeView<'a, T, S> {
+	/// Creates a new subtree view.
+	///
+	/// # Safety
+	///
+	/// The node must be a node of the given tree.
+	#[inline]
+	pub(crate) unsafe fn new(btree: &'a RawBTree<T, S>, root: S::Node) -> Self {
+		Self { btree, root }
+	}
+
+	/// Returns the root node of the subtree.
+	#[inline]
+	pub fn root(&self) -> S::Node {
+		self.root
+	}
+
+	/// Returns the separators surrounding the subtree, if any.
+	///
+	/// Every item of the subtree is strictly between the returned lower and
+	/// upper bounds.
+	pub fn key_range(&self) -> (Option<&'a T>, Option<&'a T>) {
+		let mut lower = None;
+		let mut upper = None;
+		let mut id = self.root;
+
+		unsafe {
+			while lower.is_none() || upper.is_none() {
+				let parent_id = match self.btree.nodes.get(id).parent() {
+					Some(parent_id) => parent_id,
+					None => break,
+				};
+
+				let parent = self.btree.nodes.get(parent_id);
+				let index = parent.child_index(id).unwrap();
+
+				if lower.is_none() && index > 0 {
+					lower = parent.item((index - 1).into())
+				}
+
+				if upper.is_none() {
+					upper = parent.item(index.into())
+				}
+
+				id = parent_id
+			}
+		}
+
+		(lower, upper)
+	}
+
+	fn first_item_address(&self) -> Address<S::Node> {
+		let mut id = self.root;
+		unsafe {
+			loop {
+				match self.btree.nodes.get(id).child_id_opt(0) {
+					Some(child_id) => id = child_id,
+					None => break Address::new(id, 0.into()),
+				}
+			}
+		}
+	}
+
+	fn last_item_address(&self) -> Address<S::Node> {
+		let mut id = self.root;
+		unsafe {
+			loop {
+				let node = self.btree.nodes.get(id);
+				let index = node.item_count();
+				match node.child_id_opt(index) {
+					Some(child_id) => id = child_id,
+					None => break Address::new(id, (index - 1).into()),
+				}
+			}
+		}
+	}
+
+	#[inline]
+	pub fn first(&self) -> Option<&'a T> {
+		unsafe { self.btree.get_at(self.first_item_address()) }
+	}
+
+	#[inline]
+	pub fn last(&self) -> Option<&'a T> {
+		unsafe { self.btree.get_at(self.last_item_address()) }
+	}
+
+	/// Returns the item of the subtree matching the given key, if any.
+	///
+	/// The search starts from the subtree root.
+	#[inline]
+	pub fn get<Q: ?Sized>(&self, cmp: impl Fn(&T, &Q) -> Ordering, key: &Q) -> Option<&'a T> {
+		unsafe {
+			self.btree
+				.nodes
+				.address_in(self.root, cmp, key)
+				.ok()
+				.and_then(|addr| self.btree.get_at(addr))
+		}
+	}
+
+	/// Returns an iterator over the items of the subtree, in order.
+	pub fn iter(&self) -> Iter<'a, T, S> {
+		unsafe {
+			let end = self.btree.nodes.next_item_address(self.last_item_address());
+			self.btree
+				.iter_between(Some(self.first_item_address()), end)
+		}
+	}
+}
+
+impl<'a, T, S: Storage<T>> IntoIterator for &SubtreeView<'a, T, S> {
+	type IntoIter = Iter<'a, T, S>;
+	type Item = &'a T;
+
+	#[inline]
+	fn into_iter(self) -> Iter<'a, T, S> {
+		self.iter()
+	}
+}
@@ -117,7 +117,7 @@ impl<T, const N: usize> Array<T, N> {
 		}
 	}
 
-	pub fn drain(&mut self, range: impl RangeBounds<usize>) -> Drain<T, N> {
+	pub fn drain(&mut self, range: impl RangeBounds<usize>) -> Drain<'_, T, N> {
 		let start = match range.start_bound() {
 			Bound::Unbounded => 0,
 			Bound::Included(i) => *i,
@@ -255,11 +255,7 @@ impl<'a, T, const N: usize> Iterator for Drain<'a, T, N> {
 	type Item = T;
 
 	fn size_hint(&self) -> (usize, Option<usize>) {
-		let size = if self.front < self.back {
-			self.back - self.front
-		} else {
-			0
-		};
+		let size = self.back.saturating_sub(self.front);
 
 		(size, Some(size))
 	}
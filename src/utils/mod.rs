@@ -1,19 +1,38 @@
 mod array;
-use std::cmp::Ordering;
+use std::{cmp::Ordering, ops::Bound};
 
 pub use array::Array;
 
+/// Checks that the given item is not above the given upper bound.
+#[inline]
+pub fn is_below_upper_bound<T, Q: ?Sized>(
+	cmp: impl Fn(&T, &Q) -> Ordering,
+	item: &T,
+	bound: Bound<&Q>,
+) -> bool {
+	match bound {
+		Bound::Unbounded => true,
+		Bound::Included(key) => cmp(item, key).is_le(),
+		Bound::Excluded(key) => cmp(item, key).is_lt(),
+	}
+}
+
 /// Search in `sorted_slice` for the item with the nearest key smaller or equal to the given one.
 ///
-/// `sorted_slice` is assumed to be sorted.
+/// `sorted_slice` is assumed to be sorted. Each item of the slice is compared
+/// at most once with the key.
 #[inline]
 pub fn binary_search_min<T, Q: ?Sized>(
 	cmp: impl Fn(&T, &Q) -> Ordering,
 	sorted_slice: &[T],
 	key: &Q,
 ) -> Option<(usize, bool)> {
+	if sorted_slice.is_empty() {
+		return None;
+	}
+
 	let i_ord = cmp(&sorted_slice[0], key);
-	if sorted_slice.is_empty() || i_ord.is_gt() {
+	if i_ord.is_gt() {
 		None
 	} else {
 		let mut i = 0;
@@ -1,14 +1,15 @@
 use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
-use raw_btree::{Item, RawBTree};
+use raw_btree::{Diff, Item, RawBTree};
+use std::cmp::Ordering;
 
-const SEED: &'static [u8; 32] = b"testseedtestseedtestseedtestseed";
+const SEED: &[u8; 32] = b"testseedtestseedtestseedtestseed";
 
 #[test]
 pub fn insert() {
 	let mut btree: RawBTree<Item<usize, usize>> = RawBTree::new();
 
 	for (key, value) in &ITEMS {
-		if let Some(_) = btree.insert(Item::cmp, Item::new(*key, *value)) {
+		if btree.insert(Item::cmp, Item::new(*key, *value)).is_some() {
 			println!("duplicate: {}", key);
 		}
 
@@ -18,6 +19,27 @@ pub fn insert() {
 	assert!(btree.len() == 100);
 }
 
+#[test]
+pub fn push_back() {
+	let mut btree: RawBTree<Item<usize, usize>> = RawBTree::new();
+
+	for i in 0..100 {
+		assert!(btree.push_back(Item::cmp, Item::new(i * 2, i)).is_none());
+		btree.validate(Item::cmp);
+	}
+
+	// Falls back to a regular insertion.
+	assert!(btree.push_back(Item::cmp, Item::new(51, 0)).is_none());
+	assert_eq!(
+		btree.push_back(Item::cmp, Item::new(198, 0)),
+		Some(Item::new(198, 99))
+	);
+	btree.validate(Item::cmp);
+
+	assert_eq!(btree.len(), 101);
+	assert!(btree.iter().zip(btree.iter().skip(1)).all(|(a, b)| a < b))
+}
+
 #[test]
 pub fn remove() {
 	let mut btree: RawBTree<Item<usize, usize>> = RawBTree::new();
@@ -34,7 +56,7 @@ pub fn remove() {
 	items.shuffle(&mut rng);
 
 	for (i, (key, value)) in items.iter().enumerate() {
-		let item = btree.remove(Item::key_cmp, &key);
+		let item = btree.remove(Item::key_cmp, key);
 		assert_eq!(item, Some(Item::new(*key, *value)));
 		assert_eq!(btree.len(), 99 - i);
 		btree.validate(Item::cmp);
@@ -43,6 +65,150 @@ pub fn remove() {
 	assert!(btree.is_empty())
 }
 
+#[test]
+pub fn remove_with_neighbors() {
+	let mut btree: RawBTree<Item<usize, usize>> = RawBTree::new();
+
+	let mut items = ITEMS;
+
+	for (key, value) in &items {
+		btree.insert(Item::cmp, Item::new(*key, *value));
+	}
+
+	let mut keys: Vec<usize> = items.iter().map(|(key, _)| *key).collect();
+	keys.sort();
+
+	let mut rng = SmallRng::from_seed(*SEED);
+	items.shuffle(&mut rng);
+
+	for (key, _) in &items {
+		let i = keys.binary_search(key).unwrap();
+		let previous = i.checked_sub(1).map(|j| keys[j]);
+		let next = keys.get(i + 1).copied();
+
+		let removed = btree.remove_with_neighbors(Item::key_cmp, key).unwrap();
+		assert_eq!(removed.item.key, *key);
+		assert_eq!(removed.previous.map(|item| item.key), previous);
+		assert_eq!(removed.next.map(|item| item.key), next);
+
+		keys.remove(i);
+		btree.validate(Item::cmp);
+	}
+
+	assert!(btree.is_empty())
+}
+
+#[test]
+pub fn drain_range() {
+	let mut keys: Vec<usize> = ITEMS.iter().map(|(key, _)| *key).collect();
+	keys.sort();
+
+	let ranges = [
+		(0, 10000),
+		(1000, 2000),
+		(4223, 4519),
+		(9000, 9999),
+		(4224, 4225),
+	];
+	for (start, end) in ranges {
+		for early in [false, true] {
+			let mut btree: RawBTree<Item<usize, usize>> = RawBTree::new();
+			for (key, value) in &ITEMS {
+				btree.insert(Item::cmp, Item::new(*key, *value));
+			}
+
+			let expected: Vec<usize> = keys
+				.iter()
+				.copied()
+				.filter(|key| (start..end).contains(key))
+				.collect();
+
+			let mut drain = btree.drain_range(Item::key_cmp, start..end);
+			if early {
+				drain.next();
+				std::mem::drop(drain);
+			} else {
+				let drained: Vec<usize> = drain.map(|item| item.key).collect();
+				assert_eq!(drained, expected);
+			}
+
+			btree.validate(Item::cmp);
+			assert_eq!(btree.len(), 100 - expected.len());
+			assert!(btree.iter().all(|item| !(start..end).contains(&item.key)));
+		}
+	}
+}
+
+#[test]
+pub fn compact_around() {
+	let mut btree: RawBTree<Item<usize, usize>> = RawBTree::new();
+	for i in 0..1000 {
+		btree.insert(Item::cmp, Item::new(i, i));
+	}
+
+	for i in (100..900).filter(|i| i % 10 != 0) {
+		btree.remove(Item::key_cmp, &i);
+	}
+
+	let node_count = btree.stats().node_count;
+	btree.compact_around(Item::key_cmp, &500);
+	btree.validate(Item::cmp);
+	assert!(btree.stats().node_count < node_count);
+	assert!(btree
+		.iter()
+		.map(|item| item.key)
+		.eq((0..1000).filter(|i| !(100..900).contains(i) || i % 10 == 0)));
+}
+
+#[test]
+pub fn compact_step() {
+	let mut btree: RawBTree<Item<usize, usize>> = RawBTree::new();
+	assert_eq!(unsafe { btree.compact_step(None, 10) }, None);
+
+	for i in 0..10000 {
+		btree.insert(Item::cmp, Item::new(i, i));
+	}
+
+	for i in (0..10000).filter(|i| i % 10 != 0) {
+		btree.remove(Item::key_cmp, &i);
+	}
+
+	let mut node_count = btree.stats().node_count;
+	let mut cursor = None;
+	loop {
+		cursor = unsafe { btree.compact_step(cursor, 10) };
+		btree.validate(Item::cmp);
+		let new_node_count = btree.stats().node_count;
+		assert!(new_node_count < node_count);
+		node_count = new_node_count;
+
+		// Leaves before the cursor are compacted, so that restarting the
+		// compaction from the first leaf finds the cursor.
+		assert_eq!(unsafe { btree.compact_step(None, 0) }, cursor);
+
+		if cursor.is_none() {
+			break;
+		}
+	}
+
+	btree.validate(Item::cmp);
+	assert_eq!(unsafe { btree.compact_step(None, 10) }, None);
+	assert!(btree.iter().map(|item| item.key).eq((0..10000).step_by(10)));
+}
+
+#[test]
+pub fn reset_from_sorted_iter() {
+	let mut btree: RawBTree<Item<usize, usize>> = RawBTree::new();
+	for (key, value) in &ITEMS {
+		btree.insert(Item::cmp, Item::new(*key, *value));
+	}
+
+	btree.reset_from_sorted_iter(Item::cmp, (0..10).map(|i| Item::new(i, i)));
+	btree.validate(Item::cmp);
+	assert_eq!(btree.len(), 10);
+	assert!(btree.iter().map(|item| item.key).eq(0..10));
+}
+
 #[test]
 pub fn clone() {
 	let mut btree: RawBTree<Item<usize, usize>> = RawBTree::new();
@@ -55,36 +221,187 @@ pub fn clone() {
 	cloned.validate(Item::cmp);
 }
 
-// #[test]
-// pub fn item_addresses() {
-// 	let mut btree: RawBTreeMap<usize, usize> = RawBTreeMap::new();
+#[test]
+pub fn validate_subtree() {
+	let mut btree: RawBTree<Item<usize, usize>> = RawBTree::new();
+	for (key, value) in &ITEMS {
+		btree.insert(Item::cmp, Item::new(*key, *value));
+	}
 
-// 	for (key, value) in &ITEMS {
-// 		btree.insert(usize::cmp, *key, *value);
-// 	}
+	let height = btree.height();
+	assert!(height > 1);
+	for (depth, id) in btree.bfs() {
+		assert_eq!(btree.validate_subtree(Item::cmp, id), height - 1 - depth);
+	}
+}
 
-// 	for (key, _) in &ITEMS {
-// 		let addr = btree.address_of(usize::cmp, key).ok().unwrap();
+#[test]
+pub fn raw_parts() {
+	let mut btree: RawBTree<Item<usize, usize>> = RawBTree::new();
+	for (key, value) in &ITEMS {
+		btree.insert(Item::cmp, Item::new(*key, *value));
+	}
 
-// 		match btree.previous_item_address(addr) {
-// 			Some(before_addr) => {
-// 				assert!(before_addr != addr);
-// 				let addr_again = btree.next_item_address(before_addr).unwrap();
-// 				assert_eq!(addr_again, addr)
-// 			}
-// 			None => (),
-// 		}
+	let items: Vec<_> = btree.iter().cloned().collect();
+	let (nodes, root, len) = btree.into_raw_parts();
+	assert_eq!(len, 100);
 
-// 		match btree.next_item_address(addr) {
-// 			Some(after_addr) => {
-// 				assert!(after_addr != addr);
-// 				let addr_again = btree.previous_item_address(after_addr).unwrap();
-// 				assert_eq!(addr_again, addr)
-// 			}
-// 			None => (),
-// 		}
-// 	}
-// }
+	let btree = unsafe { RawBTree::from_raw_parts(nodes, root, len) };
+	btree.validate(Item::cmp);
+	assert_eq!(btree.len(), len);
+	assert!(btree.iter().eq(&items));
+}
+
+#[test]
+pub fn address_of_cached() {
+	fn prefix_cmp(item: &Item<String, usize>, key: &str, skip: usize) -> (Ordering, usize) {
+		let a = &item.key.as_bytes()[skip..];
+		let b = &key.as_bytes()[skip..];
+		let prefix = a.iter().zip(b).take_while(|(a, b)| a == b).count();
+		(a[prefix..].cmp(&b[prefix..]), skip + prefix)
+	}
+
+	let mut btree: RawBTree<Item<String, usize>> = RawBTree::new();
+	for (key, value) in &ITEMS {
+		btree.insert(Item::cmp, Item::new(format!("key-{key}"), *value));
+	}
+
+	for (key, _) in &ITEMS {
+		for key in [
+			format!("key-{key}"),
+			format!("key-{key}0"),
+			format!("key-{}", key + 1),
+		] {
+			assert_eq!(
+				btree.address_of_cached(prefix_cmp, &key),
+				btree.address_of(Item::key_cmp, key.as_str())
+			)
+		}
+	}
+}
+
+#[test]
+pub fn get_many() {
+	let mut btree: RawBTree<Item<usize, usize>> = RawBTree::new();
+	for (key, value) in &ITEMS {
+		btree.insert(Item::cmp, Item::new(*key, *value));
+	}
+
+	let mut keys: Vec<usize> = (0..10000).step_by(7).collect();
+	keys.extend(ITEMS.iter().map(|(key, _)| *key));
+	let expected: Vec<_> = keys
+		.iter()
+		.map(|key| btree.get(Item::key_cmp, key))
+		.collect();
+	assert_eq!(btree.get_many(Item::key_cmp, &keys), expected);
+
+	keys.sort();
+	let expected: Vec<_> = keys
+		.iter()
+		.map(|key| btree.get(Item::key_cmp, key))
+		.collect();
+	assert_eq!(btree.get_many(Item::key_cmp, &keys), expected);
+
+	keys.reverse();
+	let expected: Vec<_> = expected.into_iter().rev().collect();
+	assert_eq!(btree.get_many(Item::key_cmp, &keys), expected);
+}
+
+#[test]
+pub fn stats() {
+	let mut btree: RawBTree<Item<usize, usize>> = RawBTree::new();
+	assert_eq!(btree.stats(), Default::default());
+
+	for (key, value) in &ITEMS {
+		btree.insert(Item::cmp, Item::new(*key, *value));
+	}
+
+	let stats = btree.stats();
+	assert_eq!(stats.len, 100);
+	assert_eq!(stats.height, btree.height());
+	assert_eq!(stats.node_count, stats.leaf_count + stats.internal_count);
+	assert!(stats.min_occupancy.unwrap() <= stats.max_occupancy.unwrap());
+
+	let mut node_count = 0;
+	btree.visit_from_leaves(|_| node_count += 1);
+	assert_eq!(stats.node_count, node_count);
+}
+
+#[test]
+pub fn first_difference() {
+	let mut a: RawBTree<Item<usize, usize>> = RawBTree::new();
+	for (key, value) in &ITEMS {
+		a.insert(Item::cmp, Item::new(*key, *value));
+	}
+
+	let eq = |a: &Item<usize, usize>, b: &Item<usize, usize>| a.value == b.value;
+
+	let mut b = a.clone();
+	assert_eq!(a.first_difference(Item::cmp, eq, &b), None);
+
+	b.get_mut(Item::key_cmp, &4223).unwrap().value = 0;
+	assert_eq!(
+		a.first_difference(Item::cmp, eq, &b),
+		Some(Diff::Changed(&Item::new(4223, 5948), &Item::new(4223, 0)))
+	);
+
+	b.insert(Item::cmp, Item::new(0, 0));
+	assert_eq!(
+		a.first_difference(Item::cmp, eq, &b),
+		Some(Diff::Right(&Item::new(0, 0)))
+	);
+
+	b.remove(Item::key_cmp, &0);
+	b.remove(Item::key_cmp, &4223);
+	assert_eq!(
+		a.first_difference(Item::cmp, eq, &b),
+		Some(Diff::Left(&Item::new(4223, 5948)))
+	);
+}
+
+#[test]
+pub fn diff() {
+	let mut a: RawBTree<Item<usize, usize>> = RawBTree::new();
+	for (key, value) in &ITEMS {
+		a.insert(Item::cmp, Item::new(*key, *value));
+	}
+
+	let eq = |a: &Item<usize, usize>, b: &Item<usize, usize>| a.value == b.value;
+
+	let mut b = a.clone();
+	assert_eq!(a.diff(Item::cmp, eq, &b).count(), 0);
+
+	b.get_mut(Item::key_cmp, &4223).unwrap().value = 0;
+	b.insert(Item::cmp, Item::new(0, 0));
+	b.remove(Item::key_cmp, &8175);
+	b.insert(Item::cmp, Item::new(10000, 0));
+
+	let diff: Vec<_> = a.diff(Item::cmp, eq, &b).collect();
+	assert_eq!(
+		diff,
+		[
+			Diff::Right(&Item::new(0, 0)),
+			Diff::Changed(&Item::new(4223, 5948), &Item::new(4223, 0)),
+			Diff::Left(a.get(Item::key_cmp, &8175).unwrap()),
+			Diff::Right(&Item::new(10000, 0)),
+		]
+	);
+}
+
+#[test]
+pub fn item_addresses() {
+	let mut btree: RawBTree<Item<usize, usize>> = RawBTree::new();
+
+	for (key, value) in &ITEMS {
+		btree.insert(Item::cmp, Item::new(*key, *value));
+		btree.validate_address_navigation();
+	}
+
+	for (key, _) in &ITEMS {
+		btree.remove(Item::key_cmp, key);
+		btree.validate_address_navigation();
+	}
+}
 
 // #[test]
 // pub fn valid_addresses() {
@@ -253,3 +570,1735 @@ const ITEMS: [(usize, usize); 100] = [
 	(1553, 5964),
 	(4493, 3677),
 ];
+
+#[test]
+pub fn send_sync() {
+	fn assert_send<T: Send>() {}
+	fn assert_sync<T: Sync>() {}
+
+	assert_send::<RawBTree<Item<i32, String>>>();
+	assert_sync::<RawBTree<Item<i32, String>>>();
+
+	let map: std::sync::Mutex<RawBTree<Item<i32, String>>> = std::sync::Mutex::new(RawBTree::new());
+	std::thread::scope(|s| {
+		for t in 0..4 {
+			let map = &map;
+			s.spawn(move || {
+				for i in 0..100 {
+					let key = t * 100 + i;
+					map.lock()
+						.unwrap()
+						.insert(Item::cmp, Item::new(key, key.to_string()));
+				}
+			});
+		}
+	});
+
+	let map = map.into_inner().unwrap();
+	assert_eq!(map.len(), 400);
+	map.validate(Item::cmp);
+}
+
+#[test]
+pub fn cow_snapshot() {
+	use raw_btree::storage::CowStorage;
+
+	let mut map: RawBTree<Item<i32, i32>, CowStorage<_>> = RawBTree::new();
+	for i in 0..1000 {
+		map.insert(Item::cmp, Item::new(i, i));
+	}
+
+	let snapshot = map.clone();
+
+	// Only the path to the modified leaf is copied.
+	map.insert(Item::cmp, Item::new(1, 1));
+	let ids: Vec<_> = map.bfs().map(|(_, id)| id).collect();
+	let (nodes, ..) = map.clone().into_raw_parts();
+	let (snapshot_nodes, ..) = snapshot.clone().into_raw_parts();
+	let copied = ids
+		.into_iter()
+		.filter(|id| !nodes.shares_node(&snapshot_nodes, *id))
+		.count();
+	assert_eq!(copied, map.height());
+
+	for i in 0..500 {
+		map.remove(Item::key_cmp, &(i * 2));
+	}
+	for i in 1000..1200 {
+		map.insert(Item::cmp, Item::new(i, i));
+	}
+	for item in map.iter_mut() {
+		item.value = -item.value
+	}
+
+	map.validate(Item::cmp);
+	snapshot.validate(Item::cmp);
+	assert_eq!(map.len(), 700);
+	assert!(snapshot
+		.iter()
+		.map(|item| (item.key, item.value))
+		.eq((0..1000).map(|i| (i, i))));
+	assert!(map.iter().map(|item| (item.key, item.value)).eq((0..500)
+		.map(|i| i * 2 + 1)
+		.chain(1000..1200)
+		.map(|i| (i, -i))));
+
+	map = snapshot.clone();
+	assert!(map.iter().map(|item| item.key).eq(0..1000));
+}
+
+#[test]
+pub fn update_at() {
+	let mut map: RawBTree<Item<i32, i32>> = RawBTree::new();
+	for i in 0..100 {
+		map.insert(Item::cmp, Item::new(i, i));
+	}
+
+	let addr = map.address_of(Item::key_cmp, &42).unwrap();
+	assert!(unsafe { map.update_at(addr, |item| item.value = -1) });
+	assert_eq!(map.get(Item::key_cmp, &42).unwrap().value, -1);
+
+	let back_addr = map.address_of(Item::key_cmp, &100).unwrap_err().unwrap();
+	assert!(!unsafe { map.update_at(back_addr, |_| unreachable!()) });
+	map.validate(Item::cmp);
+}
+
+#[test]
+pub fn count_matching() {
+	let mut map: RawBTree<Item<i32, i32>> = RawBTree::new();
+	assert_eq!(map.count_matching(|_| true), 0);
+
+	for i in 0..1000 {
+		map.insert(Item::cmp, Item::new(i, i));
+	}
+
+	assert_eq!(map.count_matching(|_| true), 1000);
+	assert_eq!(map.count_matching(|item| item.key % 3 == 0), 334);
+}
+
+#[test]
+pub fn bounds() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	assert_eq!(map.bounds(), None);
+
+	map.insert(Ord::cmp, 5);
+	assert_eq!(map.bounds(), Some((&5, &5)));
+
+	for i in 0..100 {
+		map.insert(Ord::cmp, i);
+	}
+	assert_eq!(map.bounds(), Some((&0, &99)));
+}
+
+#[test]
+pub fn float_keys() {
+	use raw_btree::total_f64_cmp;
+
+	let mut map: RawBTree<f64> = RawBTree::new();
+	for x in [1.5, f64::NAN, -0.0, 0.0, f64::NEG_INFINITY, -f64::NAN, 1.5] {
+		map.insert(total_f64_cmp, x);
+	}
+
+	map.validate(total_f64_cmp);
+	assert_eq!(map.len(), 6);
+	assert!(map.first().unwrap().is_nan());
+	assert!(map.last().unwrap().is_nan());
+	assert!(map.get(total_f64_cmp, &f64::NAN).is_some());
+}
+
+#[test]
+pub fn index_of_address() {
+	let mut map: RawBTree<Item<i32, i32>> = RawBTree::new();
+	for i in 0..500 {
+		map.insert(Item::cmp, Item::new(i * 2, i));
+	}
+
+	for i in 0..500 {
+		let addr = map.address_of(Item::key_cmp, &(i * 2)).unwrap();
+		assert_eq!(unsafe { map.index_of_address(addr) }, i as usize);
+
+		let addr = map
+			.address_of(Item::key_cmp, &(i * 2 + 1))
+			.unwrap_err()
+			.unwrap();
+		assert_eq!(unsafe { map.index_of_address(addr) }, i as usize + 1);
+	}
+}
+
+#[test]
+pub fn swap_items() {
+	let mut map: RawBTree<Item<i32, i32>> = RawBTree::new();
+	for i in 0..100 {
+		map.insert(Item::cmp, Item::new(i, i));
+	}
+
+	// Items comparing equal only on their keys.
+	let a = map.address_of(Item::key_cmp, &10).unwrap();
+	let b = map.address_of(Item::key_cmp, &80).unwrap();
+	unsafe {
+		map.get_mut_at(a).unwrap().key = 80;
+		map.get_mut_at(b).unwrap().key = 10;
+		map.swap_items(Item::cmp, a, b);
+	}
+
+	map.validate(Item::cmp);
+	assert_eq!(map.get(Item::key_cmp, &10).unwrap().value, 80);
+	assert_eq!(map.get(Item::key_cmp, &80).unwrap().value, 10);
+
+	// Items of the same leaf, and of the same internal node.
+	let separators: Vec<_> = map
+		.root_children()
+		.filter_map(|(_, _, separator)| separator.map(|item| item.key))
+		.collect();
+	for (x, y) in [(0, 1), (separators[0], separators[1])] {
+		let a = map.address_of(Item::key_cmp, &x).unwrap();
+		let b = map.address_of(Item::key_cmp, &y).unwrap();
+		assert_eq!(a.node, b.node);
+		unsafe {
+			map.get_mut_at(a).unwrap().key = y;
+			map.get_mut_at(b).unwrap().key = x;
+			map.swap_items(Item::cmp, a, b);
+		}
+
+		map.validate(Item::cmp);
+		assert_eq!(map.get(Item::key_cmp, &x).unwrap().value, y);
+		assert_eq!(map.get(Item::key_cmp, &y).unwrap().value, x);
+	}
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic]
+pub fn swap_items_unordered() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	for i in 0..100 {
+		map.insert(Ord::cmp, i);
+	}
+
+	let a = map.address_of(Ord::cmp, &10).unwrap();
+	let b = map.address_of(Ord::cmp, &80).unwrap();
+	unsafe { map.swap_items(Ord::cmp, a, b) }
+}
+
+#[test]
+pub fn clear_drops_items() {
+	let counter = std::rc::Rc::new(());
+	let cmp = |a: &(i32, std::rc::Rc<()>), b: &(i32, std::rc::Rc<()>)| a.0.cmp(&b.0);
+
+	let mut map: RawBTree<_> = RawBTree::new();
+	for i in 0..1000 {
+		map.insert(cmp, (i, counter.clone()));
+	}
+
+	assert_eq!(std::rc::Rc::strong_count(&counter), 1001);
+	map.clear();
+	assert_eq!(std::rc::Rc::strong_count(&counter), 1);
+	assert!(map.is_empty());
+
+	for i in 0..1000 {
+		map.insert(cmp, (i, counter.clone()));
+	}
+
+	std::mem::drop(map);
+	assert_eq!(std::rc::Rc::strong_count(&counter), 1);
+}
+
+#[test]
+pub fn insert_many_with_addresses() {
+	let mut rng = SmallRng::seed_from_u64(42);
+	let mut map: RawBTree<Item<i32, i32>> = RawBTree::new();
+	for i in 0..500 {
+		map.insert(Item::cmp, Item::new(i * 3, 0));
+	}
+
+	let mut keys: Vec<i32> = (0..1000).chain(0..100).collect();
+	keys.shuffle(&mut rng);
+
+	let addresses =
+		map.insert_many_with_addresses(Item::cmp, keys.iter().map(|k| Item::new(*k, 1)));
+	map.validate(Item::cmp);
+	assert_eq!(map.len(), 1500 - 334);
+	assert_eq!(addresses.len(), keys.len());
+	for (key, addr) in keys.iter().zip(addresses) {
+		let item = unsafe { map.get_at(addr).unwrap() };
+		assert_eq!((item.key, item.value), (*key, 1))
+	}
+}
+
+#[test]
+pub fn from_kv_iter() {
+	let map: RawBTree<Item<i32, i32>> = RawBTree::from_kv_iter((0..100).map(|i| (i, i)));
+	map.validate(Item::cmp);
+	assert!(map
+		.iter()
+		.map(|item| (item.key, item.value))
+		.eq((0..100).map(|i| (i, i))));
+
+	let map: RawBTree<Item<usize, usize>> = RawBTree::from_kv_iter(ITEMS.iter().copied());
+	map.validate(Item::cmp);
+	for (key, value) in &ITEMS {
+		assert_eq!(map.get(Item::key_cmp, key).unwrap().value, *value)
+	}
+
+	let map: RawBTree<Item<i32, i32>> = RawBTree::from_kv_iter([(1, 1), (0, 0), (1, 2)]);
+	assert_eq!(map.len(), 2);
+	assert_eq!(map.get(Item::key_cmp, &1).unwrap().value, 2);
+}
+
+#[test]
+pub fn check_parents() {
+	let mut map: RawBTree<Item<i32, i32>> = RawBTree::new();
+	assert_eq!(map.check_parents(), Ok(()));
+
+	for i in 0..1000 {
+		map.insert(Item::cmp, Item::new(i, i));
+	}
+	assert_eq!(map.check_parents(), Ok(()));
+
+	let leaf = map.first_item_address().unwrap().node;
+	let (mut nodes, root, len) = map.into_raw_parts();
+	unsafe { raw_btree::Storage::get_mut(&mut nodes, leaf).set_parent(None) };
+	let map = unsafe { RawBTree::<Item<i32, i32>>::from_raw_parts(nodes, root, len) };
+	assert_eq!(map.check_parents(), Err(leaf));
+}
+
+#[test]
+pub fn with_capacity_like() {
+	use raw_btree::storage::CowStorage;
+
+	let mut map: RawBTree<i32, CowStorage<i32>> = RawBTree::new();
+	for i in 0..1000 {
+		map.insert(Ord::cmp, i);
+	}
+	assert_eq!(map.node_count(), map.stats().node_count);
+
+	let mut other = RawBTree::with_capacity_like(&map);
+	assert!(other.is_empty());
+	for i in 0..1000 {
+		other.insert(Ord::cmp, i);
+	}
+	other.validate(Ord::cmp);
+	assert_eq!(other.node_count(), map.node_count());
+}
+
+#[test]
+pub fn splice_range() {
+	let ranges = [
+		(0, 10000),
+		(1000, 2000),
+		(4223, 4519),
+		(9000, 9999),
+		(10, 10),
+	];
+	for (start, end) in ranges {
+		let mut btree: RawBTree<Item<usize, usize>> = RawBTree::new();
+		for (key, value) in &ITEMS {
+			btree.insert(Item::cmp, Item::new(*key, *value));
+		}
+
+		let replacement: Vec<_> = (start..end).step_by(7).map(|i| Item::new(i, 0)).collect();
+		btree.splice_range(Item::cmp, Item::key_cmp, start..end, &replacement);
+		btree.validate(Item::cmp);
+
+		let mut expected: Vec<_> = ITEMS
+			.iter()
+			.map(|(key, _)| *key)
+			.filter(|key| !(start..end).contains(key))
+			.chain((start..end).step_by(7))
+			.collect();
+		expected.sort();
+		assert_eq!(btree.len(), expected.len());
+		assert!(btree.iter().map(|item| item.key).eq(expected));
+	}
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "replacement items are not sorted")]
+pub fn splice_range_unsorted() {
+	let mut btree: RawBTree<usize> = RawBTree::new();
+	for i in 0..10 {
+		btree.insert(Ord::cmp, i);
+	}
+
+	btree.splice_range(Ord::cmp, Ord::cmp, 0..10, &[5, 3]);
+}
+
+#[test]
+pub fn select_approx() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	assert_eq!(map.select_approx(0), None);
+
+	for i in 0..1000 {
+		map.insert(Ord::cmp, i * 2);
+	}
+
+	for k in 0..1000 {
+		assert_eq!(map.select_approx(k), Some(&(k as i32 * 2)))
+	}
+	assert_eq!(map.select_approx(1000), None);
+}
+
+#[test]
+#[cfg(feature = "rebalance-observer")]
+pub fn rebalance_observer() {
+	use raw_btree::RebalanceEvent;
+	use std::sync::{Arc, Mutex};
+
+	let events = Arc::new(Mutex::new((0usize, 0usize)));
+	let mut map: RawBTree<i32> = RawBTree::new();
+	let observed = events.clone();
+	map.with_rebalance_observer(move |event| {
+		let mut counts = observed.lock().unwrap();
+		match event {
+			RebalanceEvent::Split { .. } => counts.0 += 1,
+			RebalanceEvent::Merge { .. } => counts.1 += 1,
+			_ => (),
+		}
+	});
+
+	for i in 0..1000 {
+		map.insert(Ord::cmp, i);
+	}
+	assert!(events.lock().unwrap().0 > 0);
+
+	for i in 0..1000 {
+		map.remove(Ord::cmp, &i);
+		map.validate(Ord::cmp);
+	}
+	assert!(events.lock().unwrap().1 > 0);
+
+	map.clear_rebalance_observer();
+	let counts = *events.lock().unwrap();
+	for i in 0..100 {
+		map.insert(Ord::cmp, i);
+	}
+	assert_eq!(*events.lock().unwrap(), counts);
+}
+
+#[test]
+pub fn local_maxima() {
+	let mut map: RawBTree<Item<usize, i32>> = RawBTree::new();
+	let values = [5, 1, 3, 3, 2, 8, 4, 6];
+	for (key, value) in values.iter().enumerate() {
+		map.insert(Item::cmp, Item::new(key, *value));
+	}
+
+	let maxima: Vec<_> = map
+		.local_maxima(|item| item.value, Ord::cmp)
+		.map(|item| item.key)
+		.collect();
+	assert_eq!(maxima, [0, 5, 7]);
+}
+
+#[test]
+pub fn subtree_view() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	for i in 0..1000 {
+		map.insert(Ord::cmp, i);
+	}
+
+	let whole = unsafe {
+		map.subtree(map.root_children().next().unwrap().1)
+			.key_range()
+	};
+	assert_eq!(whole.0, None);
+
+	for (lower, id, upper) in map.root_children() {
+		let view = unsafe { map.subtree(id) };
+		let items: Vec<_> = view.iter().copied().collect();
+		assert_eq!(view.first(), items.first());
+		assert_eq!(view.last(), items.last());
+		assert_eq!(view.key_range(), (lower, upper));
+
+		let start = lower.map(|i| *i + 1).unwrap_or(0);
+		let end = view.key_range().1.copied().unwrap_or(1000);
+		assert!(items.iter().copied().eq(start..end));
+		assert_eq!(view.get(Ord::cmp, &start), Some(&start));
+		assert_eq!(view.get(Ord::cmp, &end), None);
+	}
+}
+
+#[test]
+pub fn offset_index() {
+	use raw_btree::{node::Offset, Address};
+
+	assert_eq!(Offset::before().to_index(), None);
+	assert_eq!(Offset::from_index(0).to_index(), Some(0));
+	assert_eq!(Offset::from_index(5).to_index(), Some(5));
+
+	let mut offset = Offset::from_index(0);
+	offset.decr();
+	assert!(offset.is_before());
+	assert_eq!(offset.to_index(), None);
+	offset.incr();
+	assert_eq!(offset.to_index(), Some(0));
+
+	assert_eq!(Address::new(0, Offset::before()).item_index(), None);
+	assert_eq!(Address::new(0, Offset::from_index(3)).item_index(), Some(3));
+}
+
+#[test]
+pub fn slice_index() {
+	use raw_btree::SliceIndex;
+
+	let records: Vec<(usize, usize)> = ITEMS.to_vec();
+	let mut index = SliceIndex::new(&records, |a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+	for i in (0..records.len()).rev() {
+		assert_eq!(index.insert(i), None);
+		index.tree().validate(|a, b| {
+			let (a, b) = (&records[*a], &records[*b]);
+			a.1.cmp(&b.1).then(a.0.cmp(&b.0))
+		});
+	}
+	assert_eq!(index.len(), records.len());
+
+	let mut sorted = records.clone();
+	sorted.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+	assert!(index.iter().map(|(_, r)| *r).eq(sorted.iter().copied()));
+
+	let (i, record) = records[10];
+	assert_eq!(
+		index.get(
+			|r, k: &(usize, usize)| r.1.cmp(&k.1).then(r.0.cmp(&k.0)),
+			&(i, record)
+		),
+		Some((10, &records[10]))
+	);
+	assert_eq!(index.remove(10), Some(10));
+	assert_eq!(index.remove(10), None);
+	assert_eq!(index.len(), records.len() - 1);
+}
+
+#[test]
+#[should_panic]
+pub fn slice_index_remove_out_of_bounds() {
+	use raw_btree::SliceIndex;
+
+	let records: Vec<usize> = vec![1, 2, 3];
+	let mut index = SliceIndex::new(&records, usize::cmp);
+	index.remove(records.len());
+}
+
+#[test]
+#[should_panic(expected = "duplicate key")]
+pub fn validate_duplicate_key() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	for i in 0..4 {
+		map.insert(Ord::cmp, i);
+	}
+
+	let addr = map.address_of(Ord::cmp, &2).unwrap();
+	unsafe { *map.get_mut_at(addr).unwrap() = 1 };
+	map.validate(Ord::cmp)
+}
+
+#[test]
+#[should_panic(expected = "not sorted")]
+pub fn validate_unsorted() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	for i in 0..4 {
+		map.insert(Ord::cmp, i);
+	}
+
+	let addr = map.address_of(Ord::cmp, &2).unwrap();
+	unsafe { *map.get_mut_at(addr).unwrap() = 0 };
+	map.validate(Ord::cmp)
+}
+
+#[test]
+pub fn insert_and_get() {
+	let mut map: RawBTree<Item<usize, usize>> = RawBTree::new();
+	for i in 0..100 {
+		let height = map.height();
+		map.insert_and_get(Item::cmp, Item::new(i * 2, 0)).value = i;
+		if i > 0 && map.height() > height {
+			// The insertion split the root.
+			assert_eq!(map.get(Item::key_cmp, &(i * 2)).unwrap().value, i);
+		}
+	}
+
+	map.validate(Item::cmp);
+	assert!(map
+		.iter()
+		.enumerate()
+		.all(|(i, item)| item.key == i * 2 && item.value == i));
+
+	*map.insert_and_get(Item::cmp, Item::new(10, 0)) = Item::new(10, 42);
+	assert_eq!(map.len(), 100);
+	assert_eq!(map.get(Item::key_cmp, &10).unwrap().value, 42);
+}
+
+#[test]
+pub fn take_while_sum() {
+	let mut map: RawBTree<u64> = RawBTree::new();
+	for i in 1..=100 {
+		map.insert(Ord::cmp, i);
+	}
+
+	let items = map.take_while_sum(|i| *i, 20);
+	assert_eq!(items.size_hint(), (0, Some(100)));
+	assert!(items.copied().eq(1..=5));
+
+	assert_eq!(map.take_while_sum(|i| *i, 0).count(), 0);
+	assert_eq!(map.take_while_sum(|_| u64::MAX, u64::MAX).count(), 1);
+	assert_eq!(map.take_while_sum(|i| *i, 5050).count(), 100);
+
+	// Lighter items following the first one exceeding the limit are skipped.
+	let mut map: RawBTree<Item<usize, u64>> = RawBTree::new();
+	for (key, weight) in [5, 100, 1].into_iter().enumerate() {
+		map.insert(Item::cmp, Item::new(key, weight));
+	}
+
+	let mut items = map.take_while_sum(|item| item.value, 10);
+	assert_eq!(items.next().map(|item| item.key), Some(0));
+	assert!(items.next().is_none());
+	assert!(items.next().is_none());
+}
+
+#[test]
+pub fn from_leaf_groups() {
+	use raw_btree::M;
+
+	let empty: RawBTree<usize> = RawBTree::from_leaf_groups(Ord::cmp, [], []);
+	assert!(empty.is_empty());
+
+	let single: RawBTree<usize> = RawBTree::from_leaf_groups(Ord::cmp, [vec![0, 1]], []);
+	single.validate(Ord::cmp);
+	assert_eq!(single.len(), 2);
+
+	for group_count in [2, 3, M, M + 1, 50, 200] {
+		let mut next = 0;
+		let mut separators = Vec::new();
+		let groups: Vec<Vec<usize>> = (0..group_count)
+			.map(|i| {
+				if i > 0 {
+					separators.push(next);
+					next += 1
+				}
+
+				let size = M / 2 + i % (M / 2 + 1);
+				let group = (next..next + size).collect();
+				next += size;
+				group
+			})
+			.collect();
+
+		let tree: RawBTree<usize> =
+			RawBTree::from_leaf_groups(Ord::cmp, groups.clone(), separators);
+		tree.validate(Ord::cmp);
+		assert!(tree.check_parents().is_ok());
+		assert_eq!(tree.len(), next);
+		assert!(tree.iter().copied().eq(0..next));
+
+		// Leaves are exactly the given groups.
+		assert!(tree
+			.leaf_ranges(|i| i)
+			.map(|(first, last)| (*first, *last))
+			.eq(groups.iter().map(|g| (g[0], g[g.len() - 1]))));
+	}
+}
+
+#[test]
+pub fn truncate() {
+	let mut map: RawBTree<Item<usize, usize>> = RawBTree::new();
+	for (key, value) in &ITEMS {
+		map.insert(Item::cmp, Item::new(*key, *value));
+	}
+
+	let mut keys: Vec<_> = ITEMS.iter().map(|(key, _)| *key).collect();
+	keys.sort();
+
+	map.truncate(200);
+	assert_eq!(map.len(), 100);
+
+	map.truncate(30);
+	map.validate(Item::cmp);
+	assert_eq!(map.len(), 30);
+	assert!(map
+		.iter()
+		.map(|item| item.key)
+		.eq(keys[..30].iter().copied()));
+
+	map.truncate(0);
+	assert!(map.is_empty());
+}
+
+#[test]
+pub fn max_value_in_range() {
+	let mut map: RawBTree<Item<usize, usize>> = RawBTree::new();
+	for (key, value) in &ITEMS {
+		map.insert(Item::cmp, Item::new(*key, *value));
+	}
+
+	for (start, end) in [(0, 10000), (1000, 2000), (4223, 4224), (5000, 5000)] {
+		let expected = ITEMS
+			.iter()
+			.filter(|(key, _)| (start..end).contains(key))
+			.max_by_key(|(_, value)| *value)
+			.map(|(key, _)| *key);
+
+		let max = map.max_value_in_range(Item::key_cmp, start..end, |item| item.value, Ord::cmp);
+		assert_eq!(max.map(|item| item.key), expected);
+	}
+}
+
+#[test]
+pub fn for_each_key() {
+	let mut map: RawBTree<Item<usize, usize>> = RawBTree::new();
+	for (key, value) in &ITEMS {
+		map.insert(Item::cmp, Item::new(*key, *value));
+	}
+
+	let mut keys = Vec::new();
+	map.for_each_key(|item| &item.key, |key| keys.push(*key));
+
+	let mut expected: Vec<_> = ITEMS.iter().map(|(key, _)| *key).collect();
+	expected.sort();
+	assert_eq!(keys, expected);
+}
+
+#[test]
+pub fn equal_range() {
+	let mut map: RawBTree<Item<usize, usize>> = RawBTree::new();
+	assert!(map.equal_range(Item::key_cmp, &0).is_none());
+
+	// Multimap: items are ordered by key then value.
+	let cmp = |a: &Item<usize, usize>, b: &Item<usize, usize>| {
+		a.key.cmp(&b.key).then(a.value.cmp(&b.value))
+	};
+	for key in 0..50 {
+		for value in 0..key % 5 {
+			map.insert(cmp, Item::new(key * 2, value));
+		}
+	}
+
+	for key in 0..100 {
+		let range = map.equal_range(Item::key_cmp, &key).unwrap();
+		let values: Vec<_> = unsafe { map.iter_between(Some(range.start), Some(range.end)) }
+			.map(|item| {
+				assert_eq!(item.key, key);
+				item.value
+			})
+			.collect();
+
+		let expected: Vec<_> = if key % 2 == 0 {
+			(0..(key / 2) % 5).collect()
+		} else {
+			Vec::new()
+		};
+		assert_eq!(values, expected);
+	}
+}
+
+#[test]
+#[cfg(feature = "rebalance-observer")]
+pub fn rebalance_counters() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	let counters = map.with_rebalance_counters();
+
+	for i in 0..1000 {
+		map.insert(Ord::cmp, i);
+	}
+	let after_inserts = *counters.lock().unwrap();
+	assert!(after_inserts.splits > 0);
+	assert_eq!(after_inserts.merges, 0);
+	// Each split adds a node, and each root split also adds a new root.
+	assert_eq!(map.stats().node_count, after_inserts.splits + map.height());
+
+	for i in (0..1000).step_by(2) {
+		map.remove(Ord::cmp, &i);
+	}
+	for i in 0..1000 {
+		map.remove(Ord::cmp, &i);
+	}
+	let totals = *counters.lock().unwrap();
+	assert!(totals.merges > 0);
+	assert!(totals.rotations > 0);
+	assert_eq!(totals.splits, after_inserts.splits);
+}
+
+#[test]
+pub fn get_compares_items_once() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	for i in 0..10000 {
+		map.insert(Ord::cmp, i * 2);
+	}
+
+	for key in [-1, 0, 1, 5000, 9999, 19998, 20000] {
+		let compared = std::cell::RefCell::new(Vec::new());
+		map.get(
+			|item, key| {
+				compared.borrow_mut().push(*item);
+				item.cmp(key)
+			},
+			&key,
+		);
+
+		let mut compared = compared.into_inner();
+		let count = compared.len();
+		compared.sort();
+		compared.dedup();
+		assert_eq!(compared.len(), count);
+	}
+}
+
+#[test]
+pub fn iter_excluding() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	let mut excluded: RawBTree<i32> = RawBTree::new();
+	for i in 0..1000 {
+		map.insert(Ord::cmp, i);
+	}
+	for i in (-10..1010).step_by(3) {
+		excluded.insert(Ord::cmp, i);
+	}
+
+	assert!(map
+		.iter_excluding(Ord::cmp, &excluded)
+		.copied()
+		.eq((0..1000).filter(|i| (i + 10) % 3 != 0)));
+	assert!(map
+		.iter_excluding(Ord::cmp, &RawBTree::new())
+		.eq(map.iter()));
+	assert_eq!(map.iter_excluding(Ord::cmp, &map).count(), 0);
+}
+
+#[test]
+pub fn insert_sorted_dedup() {
+	let mut map: RawBTree<Item<usize, usize>> = RawBTree::new();
+	for i in (0..1000).step_by(3) {
+		map.insert(Item::cmp, Item::new(i, 0));
+	}
+
+	let count = map.insert_sorted_dedup(
+		Item::cmp,
+		(0..1200).step_by(2).map(|i| Item::new(i, 1)),
+		false,
+	);
+	map.validate(Item::cmp);
+	assert_eq!(count, 600 - 167);
+	assert_eq!(map.len(), 334 + count);
+	assert!(map
+		.iter()
+		.all(|item| item.value == usize::from(item.key % 3 != 0 || item.key >= 1000)));
+
+	let count = map.insert_sorted_dedup(Item::cmp, (0..1200).map(|i| Item::new(i, 2)), true);
+	map.validate(Item::cmp);
+	assert_eq!(map.len(), 1200);
+	assert_eq!(count, 1200 - 334 - 433);
+	assert!(map
+		.iter()
+		.map(|item| (item.key, item.value))
+		.eq((0..1200).map(|i| (i, 2))));
+
+	let mut empty: RawBTree<i32> = RawBTree::new();
+	assert_eq!(empty.insert_sorted_dedup(Ord::cmp, 0..100, false), 100);
+	empty.validate(Ord::cmp);
+	assert!(empty.iter().copied().eq(0..100));
+
+	let mut map: RawBTree<Item<usize, usize>> = RawBTree::new();
+	let input = [(1, 0), (1, 1), (2, 2), (2, 3), (2, 4)];
+	let items = input.iter().map(|(key, value)| Item::new(*key, *value));
+	assert_eq!(map.insert_sorted_dedup(Item::cmp, items.clone(), false), 2);
+	assert!(map
+		.iter()
+		.map(|item| (item.key, item.value))
+		.eq([(1, 0), (2, 2)]));
+
+	map.clear();
+	assert_eq!(map.insert_sorted_dedup(Item::cmp, items, true), 2);
+	map.validate(Item::cmp);
+	assert!(map
+		.iter()
+		.map(|item| (item.key, item.value))
+		.eq([(1, 1), (2, 4)]));
+}
+
+#[test]
+pub fn root() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	assert!(map.root().is_none());
+
+	for i in 0..100 {
+		map.insert(Ord::cmp, i);
+	}
+
+	let root = map.root().unwrap();
+	assert_eq!(unsafe { map.node_depth(root) }, 0);
+	assert_eq!(map.validate_subtree(Ord::cmp, root), map.height() - 1);
+}
+
+#[test]
+pub fn bfs() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	assert_eq!(map.bfs().count(), 0);
+
+	for i in 0..1000 {
+		map.insert(Ord::cmp, i);
+	}
+
+	let nodes: Vec<_> = map.bfs().collect();
+	assert_eq!(nodes.len(), map.node_count());
+	assert_eq!(nodes[0], (0, map.root().unwrap()));
+	assert!(nodes.windows(2).all(|w| w[0].0 <= w[1].0));
+	assert_eq!(nodes.last().unwrap().0, map.height() - 1);
+	assert!(nodes
+		.iter()
+		.all(|(depth, id)| unsafe { map.node_depth(*id) } == *depth));
+}
+
+#[test]
+pub fn contains_all() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	let mut other: RawBTree<i32> = RawBTree::new();
+	assert!(map.contains_all(Ord::cmp, &other));
+
+	for i in 0..1000 {
+		map.insert(Ord::cmp, i);
+	}
+	assert!(map.contains_all(Ord::cmp, &other));
+	assert!(map.contains_all(Ord::cmp, &map));
+
+	for i in (0..1000).step_by(7) {
+		other.insert(Ord::cmp, i);
+	}
+	assert!(map.contains_all(Ord::cmp, &other));
+	assert!(!other.contains_all(Ord::cmp, &map));
+
+	other.insert(Ord::cmp, 1000);
+	assert!(!map.contains_all(Ord::cmp, &other));
+	other.remove(Ord::cmp, &1000);
+	map.remove(Ord::cmp, &500);
+	assert!(map.contains_all(Ord::cmp, &other));
+	map.remove(Ord::cmp, &497);
+	assert!(!map.contains_all(Ord::cmp, &other));
+}
+
+#[test]
+pub fn rebalance_occupancy() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	map.rebalance_occupancy();
+
+	for i in 0..10000 {
+		map.insert(Ord::cmp, i);
+	}
+	for i in 0..10000 {
+		if i % 4 != 0 {
+			map.remove(Ord::cmp, &i);
+		}
+	}
+
+	let before = map.stats();
+	map.rebalance_occupancy();
+	map.validate(Ord::cmp);
+	assert!(map.check_parents().is_ok());
+
+	let after = map.stats();
+	assert_eq!(after.len, 2500);
+	assert!(map.iter().copied().eq((0..10000).step_by(4)));
+	assert!(after.node_count < before.node_count);
+	assert!(after.avg_occupancy.unwrap() > before.avg_occupancy.unwrap());
+	assert!(after.height <= before.height);
+
+	// Sibling leaves holding `M / 2` items can't be merged pairwise, but can
+	// once items are rotated between them.
+	use raw_btree::M;
+	let (group_count, stride) = (64, M / 2 + 1);
+	let groups: Vec<Vec<usize>> = (0..group_count)
+		.map(|i| (i * stride..(i + 1) * stride - 1).collect())
+		.collect();
+	let separators = (1..group_count).map(|i| i * stride - 1);
+	let mut map: RawBTree<usize> = RawBTree::from_leaf_groups(Ord::cmp, groups, separators);
+	let before = map.stats();
+	map.rebalance_occupancy();
+	map.validate(Ord::cmp);
+	assert!(map.check_parents().is_ok());
+
+	let after = map.stats();
+	assert_eq!(after.len, group_count * stride - 1);
+	assert!(map.iter().copied().eq(0..group_count * stride - 1));
+	assert!(after.node_count < before.node_count);
+	assert!(after.avg_occupancy.unwrap() > before.avg_occupancy.unwrap());
+}
+
+#[test]
+pub fn ordered_tree() {
+	use raw_btree::OrderedTree;
+
+	let mut tree = OrderedTree::new(Item::<usize, usize>::cmp);
+	for (key, value) in &ITEMS {
+		tree.insert(Item::new(*key, *value));
+		tree.validate();
+	}
+	assert_eq!(tree.len(), 100);
+
+	let mut keys: Vec<_> = ITEMS.iter().map(|(key, _)| *key).collect();
+	keys.sort();
+	assert!(tree.iter().map(|item| item.key).eq(keys.iter().copied()));
+
+	let (key, value) = ITEMS[0];
+	assert!(tree.contains(&Item::new(key, 0)));
+	assert_eq!(tree.remove(&Item::new(key, 0)), Some(Item::new(key, value)));
+	assert!(!tree.contains(&Item::new(key, 0)));
+	assert_eq!(tree.len(), 99);
+}
+
+#[test]
+pub fn split_balanced() {
+	use raw_btree::storage::CowStorage;
+
+	for len in [0, 1, 2, 3, 8, 9, 17, 101, 1000, 4321] {
+		let mut map: RawBTree<i32> = RawBTree::new();
+		let mut cow: RawBTree<i32, CowStorage<i32>> = RawBTree::new();
+		for i in 0..len {
+			map.insert(Ord::cmp, i);
+			cow.insert(Ord::cmp, i);
+		}
+
+		let upper = map.split_balanced();
+		map.validate(Ord::cmp);
+		upper.validate(Ord::cmp);
+		assert_eq!(upper.len() as i32, len / 2);
+		assert_eq!(map.len() as i32, len - len / 2);
+		assert!(map.iter().copied().eq(0..len - len / 2));
+		assert!(upper.iter().copied().eq(len - len / 2..len));
+
+		let snapshot = cow.clone();
+		let upper = cow.split_balanced();
+		cow.validate(Ord::cmp);
+		upper.validate(Ord::cmp);
+		snapshot.validate(Ord::cmp);
+		assert!(cow.iter().chain(&upper).eq(&snapshot));
+		assert!(snapshot.iter().copied().eq(0..len));
+	}
+}
+
+#[test]
+pub fn scan_locality() {
+	use raw_btree::storage::CowStorage;
+
+	let mut map: RawBTree<i32, CowStorage<i32>> = RawBTree::new();
+	assert_eq!(map.scan_locality().transitions, 0);
+
+	for i in 0..1000 {
+		map.insert(Ord::cmp, i);
+	}
+
+	let stats = map.scan_locality();
+	assert!(stats.transitions > 0);
+	assert_eq!(stats.forward + stats.backward, stats.transitions);
+	assert!(stats.avg_distance.unwrap() >= 1.0);
+
+	let mut boxed: RawBTree<i32> = RawBTree::new();
+	boxed.insert(Ord::cmp, 0);
+	assert_eq!(boxed.scan_locality().transitions, 0);
+}
+
+#[test]
+pub fn windows_with() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	for i in 0..100 {
+		map.insert(Ord::cmp, i);
+	}
+
+	let mut sums = Vec::new();
+	map.windows_with(3, |w| sums.push(w.iter().copied().sum::<i32>()));
+	assert_eq!(sums, (1..99).map(|i| 3 * i).collect::<Vec<_>>());
+
+	let mut count = 0;
+	map.windows_with(101, |_| count += 1);
+	map.windows_with(100, |w| {
+		assert!(w.iter().copied().copied().eq(0..100));
+		count += 1
+	});
+	assert_eq!(count, 1);
+}
+
+#[test]
+pub fn range_is_empty() {
+	use std::ops::Bound;
+
+	let mut map: RawBTree<i32> = RawBTree::new();
+	assert!(map.range_is_empty(Ord::cmp, ..));
+
+	for i in 0..100 {
+		map.insert(Ord::cmp, i * 10);
+	}
+
+	assert!(!map.range_is_empty(Ord::cmp, ..));
+	assert!(!map.range_is_empty(Ord::cmp, 10..11));
+	assert!(map.range_is_empty(Ord::cmp, 11..20));
+	assert!(!map.range_is_empty(Ord::cmp, 11..=20));
+	assert!(map.range_is_empty(Ord::cmp, (Bound::Excluded(10), Bound::Excluded(20))));
+	assert!(!map.range_is_empty(Ord::cmp, (Bound::Excluded(10), Bound::Included(20))));
+	assert!(map.range_is_empty(Ord::cmp, ..0));
+	assert!(!map.range_is_empty(Ord::cmp, ..=0));
+	assert!(map.range_is_empty(Ord::cmp, 991..));
+	assert!(!map.range_is_empty(Ord::cmp, 990..));
+	for i in 0..990 {
+		assert_eq!(
+			map.range_is_empty(Ord::cmp, i..i + 5),
+			(i + 4) / 10 * 10 < i
+		);
+	}
+}
+
+#[test]
+pub fn difference_tree() {
+	let mut a: RawBTree<i32> = RawBTree::new();
+	let mut b: RawBTree<i32> = RawBTree::new();
+	for i in 0..1000 {
+		a.insert(Ord::cmp, i);
+	}
+	for i in (0..2000).step_by(2) {
+		b.insert(Ord::cmp, i);
+	}
+
+	let mut diff = a.difference_tree(Ord::cmp, &b);
+	diff.validate(Ord::cmp);
+	assert_eq!(diff.len(), 500);
+	assert!(diff.iter().copied().eq((1..1000).step_by(2)));
+
+	// The result doesn't share nodes with the inputs.
+	diff.clear();
+	a.validate(Ord::cmp);
+	assert_eq!(a.len(), 1000);
+	assert_eq!(b.difference_tree(Ord::cmp, &b).len(), 0);
+}
+
+#[test]
+pub fn find_in_range() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	for i in 0..1000 {
+		map.insert(Ord::cmp, i);
+	}
+
+	let is_error = |i: &i32| i % 97 == 0;
+	assert_eq!(map.find_in_range(Ord::cmp, 0.., is_error), Some(&0));
+	assert_eq!(map.find_in_range(Ord::cmp, 1.., is_error), Some(&97));
+	assert_eq!(map.find_in_range(Ord::cmp, 98..194, is_error), None);
+	assert_eq!(map.find_in_range(Ord::cmp, 98..=194, is_error), Some(&194));
+	assert_eq!(map.find_in_range(Ord::cmp, 971.., is_error), None);
+}
+
+#[test]
+pub fn replay() {
+	use raw_btree::LogOp;
+	use std::collections::BTreeMap;
+
+	let mut rng = SmallRng::from_seed(*SEED);
+	let mut ops = Vec::new();
+	let mut expected = BTreeMap::new();
+	for i in 0..5000 {
+		let key = ITEMS[i % 100].0;
+		if [true, false].choose(&mut rng) == Some(&true) {
+			ops.push(LogOp::Insert(Item::new(key, i)));
+			expected.insert(key, i);
+		} else {
+			ops.push(LogOp::Remove(key));
+			expected.remove(&key);
+		}
+	}
+
+	let tree: RawBTree<Item<usize, usize>> = RawBTree::replay(Item::cmp, Item::key_cmp, ops);
+	assert!(tree
+		.iter()
+		.map(|item| (item.key, item.value))
+		.eq(expected.into_iter()));
+}
+
+#[test]
+pub fn key_span() {
+	let mut map: RawBTree<Item<usize, usize>> = RawBTree::new();
+	assert_eq!(map.item_key_span(), None);
+
+	map.insert(Item::cmp, Item::new(5, 0));
+	assert_eq!(map.item_key_span(), Some((&5, &5)));
+
+	for (key, value) in &ITEMS {
+		map.insert(Item::cmp, Item::new(*key, *value));
+	}
+
+	let min = ITEMS.iter().map(|(key, _)| *key).min().unwrap().min(5);
+	let max = ITEMS.iter().map(|(key, _)| *key).max().unwrap();
+	assert_eq!(map.key_span(|item| &item.key), Some((&min, &max)));
+	assert_eq!(map.item_key_span(), Some((&min, &max)));
+}
+
+#[test]
+pub fn check_total_order() {
+	use raw_btree::{check_total_order, total_f64_cmp, OrderViolation};
+
+	let sample = [3, 1, 4, 1, 5, 9, 2, 6];
+	assert_eq!(check_total_order(i32::cmp, &sample), Ok(()));
+
+	let floats = [1.0, f64::NAN, -0.0, 0.0, f64::INFINITY];
+	assert_eq!(check_total_order(total_f64_cmp, &floats), Ok(()));
+	assert!(check_total_order(
+		|a: &f64, b: &f64| a.partial_cmp(b).unwrap_or(Ordering::Equal),
+		&floats
+	)
+	.is_err());
+
+	assert_eq!(
+		check_total_order(|_: &i32, _: &i32| Ordering::Less, &sample),
+		Err(OrderViolation::Reflexivity(0))
+	);
+	assert_eq!(
+		check_total_order(
+			|a: &i32, b: &i32| if std::ptr::eq(a, b) {
+				Ordering::Equal
+			} else {
+				a.cmp(b).then(Ordering::Less)
+			},
+			&sample
+		),
+		Err(OrderViolation::Antisymmetry(1, 3))
+	);
+
+	// Rock-paper-scissors is not transitive.
+	let rps = |a: &i32, b: &i32| match (b - a).rem_euclid(3) {
+		0 => Ordering::Equal,
+		1 => Ordering::Less,
+		_ => Ordering::Greater,
+	};
+	assert!(matches!(
+		check_total_order(rps, &[0, 1, 2]),
+		Err(OrderViolation::Transitivity(..))
+	));
+}
+
+#[test]
+pub fn take_at() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	for i in 0..1000 {
+		map.insert(Ord::cmp, i);
+	}
+
+	// Take every item from 100 to 899, chaining the addresses.
+	let mut addr = map.address_of(Ord::cmp, &100).ok();
+	let mut taken = Vec::new();
+	while let Some(a) = addr {
+		if *unsafe { map.get_at(a) }.unwrap() >= 900 {
+			break;
+		}
+
+		let (item, next) = unsafe { map.take_at_full(a) };
+		taken.push(item);
+		addr = next;
+		map.validate(Ord::cmp);
+	}
+
+	assert!(taken.into_iter().eq(100..900));
+	assert_eq!(map.len(), 200);
+	assert!(map.iter().copied().eq((0..100).chain(900..1000)));
+
+	let last = map.address_of(Ord::cmp, &999).unwrap();
+	assert_eq!(unsafe { map.take_at_full(last) }, (999, None));
+	let first = map.address_of(Ord::cmp, &0).unwrap();
+	assert_eq!(unsafe { map.take_at(first) }, 0);
+	assert_eq!(map.len(), 198);
+}
+
+#[test]
+pub fn changed_since() {
+	use raw_btree::storage::CowStorage;
+
+	let mut map: RawBTree<i32, CowStorage<i32>> = RawBTree::new();
+	for i in 0..10000 {
+		map.insert(Ord::cmp, i * 2);
+	}
+
+	let base = map.clone();
+	assert_eq!(map.changed_since(&base).count(), 0);
+
+	map.insert(Ord::cmp, 5001);
+	*map.get_mut(Ord::cmp, &8000).unwrap() = 8000;
+	let changed: Vec<_> = map.changed_since(&base).copied().collect();
+	assert!(changed.contains(&5001));
+	assert!(changed.contains(&8000));
+	assert!(changed.windows(2).all(|w| w[0] < w[1]));
+
+	// The walk skipping shared subtrees returns exactly the items of the
+	// copied nodes, which are the nodes on the paths to the two modified
+	// leaves (plus a sibling if the first leaf is split).
+	let items: Vec<_> = map.iter_with_node().map(|(id, item)| (id, *item)).collect();
+	let (nodes, ..) = map.clone().into_raw_parts();
+	let (base_nodes, ..) = base.clone().into_raw_parts();
+	let copied: Vec<_> = items
+		.iter()
+		.filter(|(id, _)| !nodes.shares_node(&base_nodes, *id))
+		.collect();
+	assert!(copied
+		.iter()
+		.map(|(_, item)| *item)
+		.eq(changed.iter().copied()));
+
+	let mut copied_nodes: Vec<_> = copied.iter().map(|(id, _)| *id).collect();
+	copied_nodes.sort();
+	copied_nodes.dedup();
+	assert!(copied_nodes.len() <= 2 * map.height() + 1);
+
+	// The base is unchanged.
+	assert!(base.iter().copied().eq((0..10000).map(|i| i * 2)));
+}
+
+#[test]
+pub fn reserve_insertion() {
+	let mut map: RawBTree<Item<usize, usize>> = RawBTree::new();
+	for (key, value) in &ITEMS {
+		match map.reserve_insertion(Item::key_cmp, key) {
+			Ok(slot) => {
+				unsafe { slot.commit(Item::new(*key, 0)) }.value = *value;
+			}
+			Err(entry) => panic!("unexpected entry {}", entry.get()),
+		}
+
+		map.validate(Item::cmp);
+	}
+	assert_eq!(map.len(), 100);
+
+	let (key, value) = ITEMS[0];
+	match map.reserve_insertion(Item::key_cmp, &key) {
+		Ok(_) => panic!("unexpected slot"),
+		Err(entry) => assert_eq!(entry.get().value, value),
+	}
+
+	map.reserve_insertion(Item::key_cmp, &0)
+		.ok()
+		.unwrap()
+		.abort();
+	assert_eq!(map.len(), 100);
+	assert!(map.get(Item::key_cmp, &0).is_none());
+}
+
+#[test]
+pub fn leaf_ranges() {
+	let mut btree: RawBTree<usize> = RawBTree::new();
+	assert_eq!(btree.leaf_ranges(|i| i).count(), 0);
+
+	btree.insert(usize::cmp, 7);
+	assert!(btree.leaf_ranges(|i| i).eq([(&7, &7)]));
+
+	for i in 0..100 {
+		btree.insert(usize::cmp, i);
+	}
+
+	let ranges: Vec<_> = btree.leaf_ranges(|i| i).collect();
+	assert!(ranges.len() > 1);
+	assert_eq!(ranges.first().unwrap().0, &0);
+	assert_eq!(ranges.last().unwrap().1, &99);
+	assert!(ranges.iter().all(|(min, max)| min <= max));
+	assert!(ranges.windows(2).all(|w| w[0].1 < w[1].0));
+
+	// Items are contiguous, so every item outside a leaf range is a separator.
+	let leaf_items: usize = ranges.iter().map(|(min, max)| *max - *min + 1).sum();
+	assert_eq!(leaf_items + btree.separators().count(), btree.len());
+}
+
+#[test]
+pub fn self_check_count() {
+	let mut btree: RawBTree<usize> = RawBTree::new();
+	assert!(btree.self_check_count());
+
+	for i in 0..100 {
+		btree.insert(usize::cmp, i);
+	}
+	assert!(btree.self_check_count());
+
+	for len in [99, 101] {
+		let (nodes, root, actual_len) = btree.into_raw_parts();
+		let corrupted = unsafe { RawBTree::from_raw_parts(nodes, root, len) };
+		assert!(!corrupted.self_check_count());
+
+		let (nodes, root, _) = corrupted.into_raw_parts();
+		btree = unsafe { RawBTree::from_raw_parts(nodes, root, actual_len) };
+	}
+
+	assert!(btree.self_check_count());
+}
+
+#[test]
+pub fn from_sorted_vec() {
+	for n in [0, 1, 7, 8, 9, 10, 17, 18, 19, 100, 1000] {
+		let items: Vec<String> = (0..n).map(|i| format!("{i:04}")).collect();
+		let tree: RawBTree<String> = RawBTree::from_sorted_vec(Ord::cmp, items.clone());
+		tree.validate(Ord::cmp);
+		assert!(tree.check_parents().is_ok());
+		assert!(tree.self_check_count());
+		assert_eq!(tree.len(), n);
+		assert!(tree.iter().eq(items.iter()));
+	}
+
+	let checked: RawBTree<usize> = RawBTree::try_from_sorted_vec(Ord::cmp, vec![1, 2, 3]).unwrap();
+	assert!(checked.iter().copied().eq([1, 2, 3]));
+
+	let unsorted = vec![1, 3, 2];
+	assert_eq!(
+		RawBTree::<usize>::try_from_sorted_vec(Ord::cmp, unsorted.clone()).err(),
+		Some(unsorted)
+	);
+	assert!(RawBTree::<usize>::try_from_sorted_vec(Ord::cmp, vec![1, 1]).is_err());
+}
+
+#[test]
+pub fn common_depth() {
+	let mut btree: RawBTree<usize> = RawBTree::new();
+	assert_eq!(btree.common_depth(usize::cmp, &1, &2), 0);
+
+	for i in (0..1000).map(|i| i * 2) {
+		btree.insert(usize::cmp, i);
+	}
+
+	let height = btree.height();
+	assert!(height > 2);
+
+	// Missing keys landing in the same leaf share the whole path.
+	assert_eq!(btree.common_depth(usize::cmp, &1, &1), height);
+	assert_eq!(btree.common_depth(usize::cmp, &1, &3), height);
+
+	// The smallest and largest keys diverge at the root.
+	assert_eq!(btree.common_depth(usize::cmp, &1, &1999), 0);
+
+	// A key found in a node stops its search there.
+	let root_item = btree.root_children().next().unwrap().2.copied().unwrap();
+	assert_eq!(btree.common_depth(usize::cmp, &root_item, &root_item), 0);
+
+	// Keys stored in the same leaf share the whole path.
+	let (leaf_id, first) = btree.iter_with_node().next().unwrap();
+	let second = btree
+		.iter_with_node()
+		.nth(1)
+		.filter(|(id, _)| *id == leaf_id)
+		.unwrap()
+		.1;
+	assert_eq!(btree.common_depth(usize::cmp, first, second), height);
+	assert_eq!(btree.common_depth(usize::cmp, first, &(first + 1)), height);
+}
+
+#[test]
+pub fn reverse() {
+	use raw_btree::{reverse_cmp, Reverse};
+
+	let mut btree: RawBTree<Reverse<usize>> = RawBTree::new();
+	for (key, _) in &ITEMS {
+		btree.insert(reverse_cmp, Reverse(*key));
+	}
+	btree.validate(reverse_cmp);
+
+	let mut keys: Vec<_> = ITEMS.iter().map(|(key, _)| *key).collect();
+	keys.sort();
+	assert!(btree
+		.iter()
+		.map(|item| item.0)
+		.eq(keys.iter().rev().copied()));
+	assert!(Reverse(2) < Reverse(1));
+
+	assert_eq!(btree.pop_first(), Some(Reverse(keys[keys.len() - 1])));
+	assert_eq!(btree.pop_last(), Some(Reverse(keys[0])));
+	btree.validate(reverse_cmp);
+}
+
+#[test]
+pub fn offset_from_index() {
+	use raw_btree::node::Offset;
+
+	assert_eq!(Offset::from_index(3).to_index(), Some(3));
+	assert_eq!(Offset::before().to_index(), None);
+}
+
+#[test]
+#[cfg(feature = "small-offset")]
+#[should_panic(expected = "offset out of bounds")]
+pub fn small_offset_overflow() {
+	raw_btree::node::Offset::from_index(u16::MAX as usize + 1);
+}
+
+#[test]
+#[cfg(feature = "rayon")]
+pub fn par_clear() {
+	let mut map: RawBTree<String> = RawBTree::new();
+	for i in 0..10000 {
+		map.insert(Ord::cmp, format!("{i:05}"));
+	}
+	assert!(map.height() > 2);
+
+	map.par_clear();
+	assert_eq!(map.len(), 0);
+	assert!(map.is_empty());
+	assert!(map.iter().next().is_none());
+
+	for i in 0..100 {
+		map.insert(Ord::cmp, format!("{i:05}"));
+	}
+	map.validate(Ord::cmp);
+	assert_eq!(map.len(), 100);
+}
+
+#[test]
+pub fn sequential_split_bias() {
+	use raw_btree::M;
+
+	let mut map: RawBTree<usize> = RawBTree::new();
+	for i in 0..2000 {
+		map.insert(Ord::cmp, i);
+		map.validate(Ord::cmp);
+	}
+
+	for i in 2000..10000 {
+		map.insert(Ord::cmp, i);
+	}
+	map.validate(Ord::cmp);
+
+	// Splitting in the middle would leave every leaf but the last one with
+	// `M / 2` items, each followed by a separator, and at least one parent
+	// node for every `M` leaves.
+	let stats = map.stats();
+	let mid_leaf_count = 10000 / (M / 2 + 1);
+	assert!(stats.leaf_count < mid_leaf_count);
+	assert!(stats.node_count < mid_leaf_count + mid_leaf_count / M);
+}
+
+#[test]
+pub fn rebalance_from() {
+	use raw_btree::{node::Address, Storage, M};
+
+	let mut map: RawBTree<usize> = RawBTree::new();
+	for i in 1..100 {
+		map.insert(Ord::cmp, i * 100);
+	}
+
+	let leaf = map.address_of(Ord::cmp, &0).unwrap_err().unwrap().node;
+	let (mut nodes, root, mut len) = map.into_raw_parts();
+
+	// Overflow the first leaf by inserting items directly into it.
+	let mut key = 0;
+	while unsafe { nodes.get(leaf).item_count() } <= M {
+		unsafe { nodes.get_mut(leaf).insert(key.into(), key, None) };
+		key += 1;
+		len += 1;
+	}
+
+	let last = key - 1;
+	let mut map = unsafe { RawBTree::from_raw_parts(nodes, root, len) };
+	let addr = unsafe { map.rebalance_from(leaf, Address::new(leaf, last.into())) };
+	map.validate(Ord::cmp);
+	assert_eq!(map.len(), len);
+	assert_eq!(unsafe { map.get_at(addr.unwrap()) }, Some(&last));
+	assert!(map
+		.iter()
+		.copied()
+		.eq((0..key).chain((1..100).map(|i| i * 100))));
+}
+
+#[test]
+pub fn structural_hash() {
+	let mut inserted: RawBTree<usize> = RawBTree::new();
+	for i in 0..1000 {
+		inserted.insert(Ord::cmp, i);
+	}
+
+	let bulk: RawBTree<usize> = RawBTree::from_sorted_vec(Ord::cmp, (0..1000).collect());
+	assert!(inserted.iter().eq(bulk.iter()));
+	assert_ne!(inserted.node_count(), bulk.node_count());
+	assert_ne!(inserted.structural_hash(), bulk.structural_hash());
+
+	assert_eq!(
+		inserted.structural_hash(),
+		inserted.clone().structural_hash()
+	);
+	assert_eq!(
+		RawBTree::<usize>::new().structural_hash(),
+		RawBTree::<usize>::new().structural_hash()
+	);
+}
+
+#[test]
+pub fn item_at_or_before_index() {
+	let mut map: RawBTree<usize> = RawBTree::new();
+	assert_eq!(map.item_at_or_before_index(0), None);
+	assert_eq!(map.item_at_or_before_index(10), None);
+
+	for i in 0..100 {
+		map.insert(Ord::cmp, i * 2);
+	}
+
+	assert_eq!(map.item_at_or_before_index(0), Some((0, &0)));
+	assert_eq!(map.item_at_or_before_index(42), Some((42, &84)));
+	assert_eq!(map.item_at_or_before_index(99), Some((99, &198)));
+	assert_eq!(map.item_at_or_before_index(100), Some((99, &198)));
+	assert_eq!(map.item_at_or_before_index(usize::MAX), Some((99, &198)));
+}
+
+#[test]
+pub fn replace_at_checked() {
+	let mut map: RawBTree<Item<usize, usize>> = RawBTree::new();
+	for i in 0..100 {
+		map.insert(Item::cmp, Item::new(i, 0));
+	}
+
+	let addr = map.address_of(Item::key_cmp, &42).unwrap();
+	let old = unsafe { map.replace_at_checked(addr, Item::new(42, 1)) };
+	assert_eq!(old.ok().map(|item| item.value), Some(0));
+	assert_eq!(map.get(Item::key_cmp, &42).unwrap().value, 1);
+
+	let back = map.address_of(Item::key_cmp, &100).unwrap_err().unwrap();
+	let rejected = unsafe { map.replace_at_checked(back, Item::new(100, 2)) };
+	assert_eq!(
+		rejected.err().map(|item| (item.key, item.value)),
+		Some((100, 2))
+	);
+	assert_eq!(map.len(), 100);
+	map.validate(Item::cmp);
+}
+
+#[test]
+pub fn address() {
+	let mut map: RawBTree<usize> = RawBTree::new();
+	for i in 0..100 {
+		map.insert(Ord::cmp, i);
+	}
+
+	let addr = map.address_of(Ord::cmp, &42).unwrap();
+	unsafe {
+		let item_count = map.node_addresses(addr.node).count();
+
+		let first = map.address(addr.node, 0).unwrap();
+		assert!(map.get_at(first).is_some());
+
+		// The back address of the node is valid, but holds no item.
+		let back = map.address(addr.node, item_count).unwrap();
+		assert_eq!(back.offset, item_count);
+		assert!(map.get_at(back).is_none());
+
+		assert_eq!(map.address(addr.node, item_count + 1), None);
+	}
+}
+
+#[test]
+pub fn node_addresses() {
+	let mut map: RawBTree<usize> = RawBTree::new();
+	for i in 0..1000 {
+		map.insert(Ord::cmp, i);
+	}
+
+	let mut items = Vec::new();
+	for (_, id) in map.bfs() {
+		let addresses: Vec<_> = unsafe { map.node_addresses(id) }.collect();
+		assert!(!addresses.is_empty());
+		for (offset, addr) in addresses.iter().enumerate() {
+			assert_eq!(addr.node, id);
+			assert_eq!(addr.offset, offset);
+			assert_eq!(
+				Ok(*addr),
+				map.address_of(Ord::cmp, unsafe { map.get_at(*addr).unwrap() })
+			);
+		}
+
+		items.extend(
+			addresses
+				.iter()
+				.map(|addr| unsafe { *map.get_at(*addr).unwrap() }),
+		);
+	}
+
+	items.sort();
+	assert!(items.into_iter().eq(0..1000));
+}
+
+#[test]
+pub fn is_leaf_address() {
+	let mut map: RawBTree<usize> = RawBTree::new();
+	for i in 0..1000 {
+		map.insert(Ord::cmp, i);
+	}
+
+	let root_item = *map.root_children().next().unwrap().2.unwrap();
+	let internal = map.address_of(Ord::cmp, &root_item).unwrap();
+	assert!(!unsafe { map.is_leaf_address(internal) });
+
+	let leaf = map.address_of(Ord::cmp, &0).unwrap();
+	assert!(unsafe { map.is_leaf_address(leaf) });
+}
+
+#[test]
+pub fn next_item_or_back_address() {
+	let mut map: RawBTree<usize> = RawBTree::new();
+	for i in 0..1000 {
+		map.insert(Ord::cmp, i);
+	}
+
+	unsafe {
+		for i in 0..999 {
+			let addr = map.address_of(Ord::cmp, &i).unwrap();
+			let next = map.next_item_or_back_address(addr).unwrap();
+			assert_eq!(map.get_at(next), Some(&(i + 1)));
+		}
+
+		// After the last item comes the back address of its leaf.
+		let last = map.address_of(Ord::cmp, &999).unwrap();
+		let back = map.next_item_or_back_address(last).unwrap();
+		assert_eq!(back.node, last.node);
+		assert_eq!(back.offset, last.offset.unwrap() + 1);
+		assert!(map.get_at(back).is_none());
+	}
+}
+
+#[test]
+pub fn try_visit_from_leaves() {
+	use std::ops::ControlFlow;
+
+	let mut map: RawBTree<usize> = RawBTree::new();
+	for i in 0..1000 {
+		map.insert(Ord::cmp, i);
+	}
+
+	let mut all = Vec::new();
+	map.visit_from_leaves(|id| all.push(id));
+
+	let mut visited = Vec::new();
+	let result = map.try_visit_from_leaves(|id| {
+		visited.push(id);
+		if visited.len() == 10 {
+			ControlFlow::Break(id)
+		} else {
+			ControlFlow::Continue(())
+		}
+	});
+	assert_eq!(result, ControlFlow::Break(all[9]));
+	assert_eq!(visited, all[..10]);
+
+	let mut count = 0;
+	let result: ControlFlow<()> = map.try_visit_from_leaves(|_| {
+		count += 1;
+		ControlFlow::Continue(())
+	});
+	assert_eq!(result, ControlFlow::Continue(()));
+	assert_eq!(count, all.len());
+}
+
+#[test]
+pub fn insertion_node() {
+	let mut map: RawBTree<usize> = RawBTree::new();
+	assert_eq!(map.insertion_node(Ord::cmp, &0), None);
+
+	for i in 0..1000 {
+		map.insert(Ord::cmp, i * 2);
+	}
+
+	// A present key is found in its node, which may be internal.
+	for key in [0, 500, 1998] {
+		let addr = map.address_of(Ord::cmp, &key).unwrap();
+		assert_eq!(map.insertion_node(Ord::cmp, &key), Some(addr.node));
+	}
+
+	let root_item = *map.root_children().next().unwrap().2.unwrap();
+	let root = map.root().unwrap();
+	assert_eq!(map.insertion_node(Ord::cmp, &root_item), Some(root));
+
+	// A missing key is inserted in a leaf.
+	let addr = map.address_of(Ord::cmp, &501).unwrap_err().unwrap();
+	assert_eq!(map.insertion_node(Ord::cmp, &501), Some(addr.node));
+	assert!(unsafe { map.is_leaf_address(addr) });
+}
@@ -1,6 +1,6 @@
 use std::{cell::Cell, rc::Rc};
 
-use raw_btree::{Item, RawBTree};
+use raw_btree::{storage::CowStorage, Item, RawBTree};
 
 #[test]
 pub fn iter() {
@@ -189,3 +189,399 @@ pub fn into_iter_both_ends2() {
 
 	assert_eq!(counter.get(), 100);
 }
+
+#[test]
+pub fn first_last_entry() {
+	let mut map: RawBTree<Item<i32, i32>> = RawBTree::new();
+	for i in 0..100 {
+		map.insert(Item::cmp, Item::new(i, i));
+	}
+
+	map.first_entry().unwrap().get_mut().value = 42;
+	assert_eq!(map.first().unwrap().value, 42);
+
+	while let Some(entry) = map.last_entry() {
+		if entry.get().key < 50 {
+			break;
+		}
+
+		entry.remove();
+	}
+
+	assert_eq!(map.len(), 50);
+	assert_eq!(map.last().unwrap().key, 49);
+	map.validate(Item::cmp);
+
+	assert_eq!(map.pop_back().unwrap().key, 49);
+	assert_eq!(map.pop_front().unwrap().key, 0);
+
+	while let Some(entry) = map.first_entry() {
+		entry.remove();
+	}
+
+	assert!(map.is_empty())
+}
+
+#[test]
+pub fn windows2() {
+	let mut map: RawBTree<Item<i32, i32>> = RawBTree::new();
+	assert!(map.windows2(|_, _| false));
+
+	for i in 0..100 {
+		map.insert(Item::cmp, Item::new(i, i));
+	}
+
+	assert!(map.windows2(|a, b| b.key - a.key == 1));
+	map.remove(Item::key_cmp, &50);
+	assert!(!map.windows2(|a, b| b.key - a.key == 1));
+}
+
+#[test]
+pub fn iter_with_depth() {
+	let mut map: RawBTree<Item<i32, i32>> = RawBTree::new();
+	for i in 0..100 {
+		map.insert(Item::cmp, Item::new(i, i));
+	}
+
+	let height = map.height();
+	let mut i = 0;
+	for (depth, item) in map.iter_with_depth() {
+		assert_eq!(item.key, i);
+		assert!(depth < height);
+		i += 1;
+	}
+
+	assert_eq!(i, 100);
+
+	let root_items = map.iter_with_depth().filter(|(d, _)| *d == 0).count();
+	assert!(root_items > 0 && root_items < raw_btree::M);
+}
+
+#[test]
+pub fn separators() {
+	let mut map: RawBTree<Item<i32, i32>> = RawBTree::new();
+	for i in 0..5 {
+		map.insert(Item::cmp, Item::new(i, i));
+	}
+
+	assert_eq!(map.separators().count(), 0);
+
+	for i in 5..1000 {
+		map.insert(Item::cmp, Item::new(i, i));
+	}
+
+	let separators: Vec<_> = map.separators().map(|item| item.key).collect();
+	let expected: Vec<_> = map
+		.iter_with_depth()
+		.filter(|(depth, _)| *depth + 1 < map.height())
+		.map(|(_, item)| item.key)
+		.collect();
+	assert!(!separators.is_empty());
+	assert_eq!(separators, expected);
+}
+
+#[test]
+pub fn iter_merged() {
+	let mut map: RawBTree<Item<i32, i32>> = RawBTree::new();
+	for i in (0..100).step_by(2) {
+		map.insert(Item::cmp, Item::new(i, i));
+	}
+
+	let pending: Vec<_> = (0..100).step_by(3).map(|i| Item::new(i, -i)).collect();
+	let merged: Vec<_> = map
+		.iter_merged(Item::cmp, &pending)
+		.map(|item| (item.key, item.value))
+		.collect();
+
+	let expected: Vec<_> = (0..100)
+		.filter_map(|i| {
+			if i % 3 == 0 {
+				Some((i, -i))
+			} else if i % 2 == 0 {
+				Some((i, i))
+			} else {
+				None
+			}
+		})
+		.collect();
+
+	assert_eq!(merged, expected)
+}
+
+#[test]
+pub fn longest_run() {
+	let mut map: RawBTree<Item<i32, i32>> = RawBTree::new();
+	assert_eq!(map.longest_run(|_| true), None);
+
+	for i in 0..100 {
+		map.insert(Item::cmp, Item::new(i, i));
+	}
+
+	assert_eq!(map.longest_run(|_| false), None);
+	assert_eq!(map.longest_run(|_| true), Some((0, 100)));
+	assert_eq!(
+		map.longest_run(|item| (10..15).contains(&item.key) || (40..60).contains(&item.key)),
+		Some((40, 20))
+	);
+}
+
+#[test]
+pub fn iter_between() {
+	let mut map: RawBTree<Item<i32, i32>> = RawBTree::new();
+	for i in 0..100 {
+		map.insert(Item::cmp, Item::new(i, i));
+	}
+
+	let start = map.lower_bound_address(Item::key_cmp, std::ops::Bound::Included(&20));
+	let end = map.lower_bound_address(Item::key_cmp, std::ops::Bound::Included(&70));
+
+	let iter = unsafe { map.iter_between(start, end) };
+	assert_eq!(iter.len(), 50);
+	assert!(iter.map(|item| item.key).eq(20..70));
+
+	let iter = unsafe { map.iter_between(start, end) };
+	assert!(iter.rev().map(|item| item.key).eq((20..70).rev()));
+
+	let iter = unsafe { map.iter_between(None, end) };
+	assert!(iter.map(|item| item.key).eq(0..70));
+
+	let iter = unsafe { map.iter_between(start, None) };
+	assert!(iter.map(|item| item.key).eq(20..100));
+}
+
+#[test]
+pub fn gaps() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	for i in [1, 2, 5, 6, 10] {
+		map.insert(Ord::cmp, i);
+	}
+
+	let gaps: Vec<_> = map.gaps(|i| i.checked_add(1), |a, b| a + 1 == *b).collect();
+	assert_eq!(gaps, [(2, 5), (6, 10)]);
+
+	let mut map: RawBTree<i32> = RawBTree::new();
+	for i in 0..100 {
+		map.insert(Ord::cmp, i);
+	}
+
+	assert_eq!(
+		map.gaps(|i| i.checked_add(1), |a, b| a + 1 == *b).count(),
+		0
+	)
+}
+
+#[test]
+pub fn range_prefix() {
+	let mut map: RawBTree<String> = RawBTree::new();
+	for a in ["", "a", "b", "\u{d7ff}", "\u{10ffff}"] {
+		for b in ["", "a", "ab", "b", "\u{d7ff}", "\u{e000}", "\u{10ffff}"] {
+			map.insert(Ord::cmp, format!("{a}{b}"));
+		}
+	}
+
+	let cmp = |item: &String, key: &str| item.as_str().cmp(key);
+	for prefix in ["", "a", "ab", "b", "c", "\u{d7ff}", "\u{10ffff}"] {
+		let expected: Vec<_> = map.iter().filter(|s| s.starts_with(prefix)).collect();
+		let found: Vec<_> = map.range_prefix(cmp, prefix).collect();
+		assert_eq!(found, expected)
+	}
+
+	let mut map: RawBTree<Vec<u8>> = RawBTree::new();
+	for a in [0u8, 1, 0xfe, 0xff] {
+		for b in [0u8, 1, 0xfe, 0xff] {
+			map.insert(Ord::cmp, vec![a]);
+			map.insert(Ord::cmp, vec![a, b]);
+			map.insert(Ord::cmp, vec![a, b, 0xff]);
+		}
+	}
+
+	let cmp = |item: &Vec<u8>, key: &[u8]| item.as_slice().cmp(key);
+	for prefix in [
+		&[][..],
+		&[0],
+		&[0xfe],
+		&[0xff],
+		&[0xff, 0xff],
+		&[0xfe, 0xff],
+	] {
+		let expected: Vec<_> = map.iter().filter(|s| s.starts_with(prefix)).collect();
+		let found: Vec<_> = map.range_prefix(cmp, prefix).collect();
+		assert_eq!(found, expected)
+	}
+}
+
+#[test]
+pub fn chunks() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	assert_eq!(map.chunks(3).count(), 0);
+
+	for i in 0..100 {
+		map.insert(Ord::cmp, i);
+	}
+
+	let chunks: Vec<Vec<i32>> = map.chunks(30).map(|c| c.copied().collect()).collect();
+	assert_eq!(chunks.len(), 4);
+	assert!(chunks.iter().flatten().copied().eq(0..100));
+	assert_eq!(chunks[3], (90..100).collect::<Vec<_>>());
+}
+
+#[test]
+pub fn iter_mut_unordered() {
+	let mut map: RawBTree<Item<i32, i32>> = RawBTree::new();
+	for i in 0..1000 {
+		map.insert(Item::cmp, Item::new(i, i));
+	}
+
+	let mut count = 0;
+	for item in map.iter_mut_unordered() {
+		item.value *= 2;
+		count += 1
+	}
+
+	assert_eq!(count, 1000);
+	assert!(map
+		.iter()
+		.map(|item| item.value)
+		.eq((0..1000).map(|i| i * 2)));
+
+	// Nodes are visited in storage order, copying the nodes shared with a
+	// clone.
+	let mut map: RawBTree<Item<i32, i32>, CowStorage<_>> = RawBTree::new();
+	for i in 0..1000 {
+		map.insert(Item::cmp, Item::new(i, i));
+	}
+	for i in (0..1000).step_by(3) {
+		map.remove(Item::key_cmp, &i);
+	}
+
+	let base = map.clone();
+	let mut keys: Vec<_> = map
+		.iter_mut_unordered()
+		.map(|item| {
+			item.value *= 2;
+			item.key
+		})
+		.collect();
+	keys.sort();
+
+	map.validate(Item::cmp);
+	assert!(keys.iter().eq(base.iter().map(|item| &item.key)));
+	assert!(map.iter().all(|item| item.value == item.key * 2));
+	assert!(base.iter().all(|item| item.value == item.key));
+	assert_eq!(map.changed_since(&base).count(), map.len());
+}
+
+#[test]
+pub fn within() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	for i in 0..100 {
+		map.insert(Ord::cmp, i * 2);
+	}
+
+	let found: Vec<_> = map
+		.within(Ord::cmp, &50, |i| (i - 50).abs() <= 6)
+		.copied()
+		.collect();
+	assert_eq!(found, [50, 52, 48, 54, 46, 56, 44]);
+
+	let found: Vec<_> = map
+		.within(Ord::cmp, &51, |i| (i - 51).abs() <= 4)
+		.copied()
+		.collect();
+	assert_eq!(found, [52, 50, 54, 48]);
+
+	let found: Vec<_> = map.within(Ord::cmp, &2, |i| *i <= 10).copied().collect();
+	assert_eq!(found, [2, 4, 0, 6, 8, 10]);
+
+	assert_eq!(map.within(Ord::cmp, &500, |_| true).count(), 100);
+}
+
+#[test]
+pub fn iter_with_node() {
+	let mut map: RawBTree<Item<i32, i32>> = RawBTree::new();
+	for i in 0..100 {
+		map.insert(Item::cmp, Item::new(i, i));
+	}
+
+	let mut i = 0;
+	for (node, item) in map.iter_with_node() {
+		assert_eq!(item.key, i);
+		let addr = map.address_of(Item::key_cmp, &i).unwrap();
+		assert_eq!(node, addr.node);
+		i += 1;
+	}
+
+	assert_eq!(i, 100);
+}
+
+#[test]
+pub fn coalesce() {
+	let mut map: RawBTree<(i32, i32)> = RawBTree::new();
+	for interval in [(0, 2), (2, 5), (7, 8), (8, 9), (9, 12), (20, 21)] {
+		map.insert(Ord::cmp, interval);
+	}
+
+	let merged: Vec<_> = map
+		.coalesce(|a, b| (a.1 == b.0).then_some((a.0, b.1)))
+		.collect();
+	assert!(matches!(merged[2], std::borrow::Cow::Borrowed(_)));
+	let merged: Vec<_> = merged.into_iter().map(|i| i.into_owned()).collect();
+	assert_eq!(merged, [(0, 5), (7, 12), (20, 21)]);
+
+	let map: RawBTree<(i32, i32)> = RawBTree::new();
+	assert_eq!(map.coalesce(|_, _| None).count(), 0)
+}
+
+#[test]
+pub fn root_children() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	assert_eq!(map.root_children().count(), 0);
+
+	map.insert(Ord::cmp, 0);
+	assert_eq!(map.root_children().count(), 0);
+
+	for i in 1..1000 {
+		map.insert(Ord::cmp, i);
+	}
+
+	let children: Vec<_> = map.root_children().collect();
+	assert!(children.len() >= 2);
+	assert_eq!(children.first().unwrap().0, None);
+	assert_eq!(children.last().unwrap().2, None);
+	for w in children.windows(2) {
+		assert_eq!(w[0].2, w[1].0);
+	}
+
+	let separators: Vec<_> = map
+		.iter_with_depth()
+		.filter(|(d, _)| *d == 0)
+		.map(|(_, i)| i)
+		.collect();
+	let found: Vec<_> = children.iter().filter_map(|c| c.2).collect();
+	assert_eq!(found, separators);
+}
+
+#[test]
+pub fn iter_enumerated() {
+	let mut map: RawBTree<i32> = RawBTree::new();
+	for i in 0..100 {
+		map.insert(Ord::cmp, i * 3);
+	}
+
+	assert!(map.iter_enumerated().all(|(i, item)| *item == i as i32 * 3));
+	assert!(map
+		.iter_enumerated()
+		.rev()
+		.all(|(i, item)| *item == i as i32 * 3));
+	assert_eq!(map.iter_enumerated().next_back().unwrap().0, 99);
+
+	let mut items = map.iter_enumerated();
+	assert_eq!(items.next(), Some((0, &0)));
+	assert_eq!(items.next_back(), Some((99, &297)));
+	assert_eq!(items.next_back(), Some((98, &294)));
+	assert_eq!(items.next(), Some((1, &3)));
+
+	for (i, item) in map.iter_enumerated() {
+		assert_eq!(map.select_approx(i), Some(item));
+	}
+}